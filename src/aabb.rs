@@ -0,0 +1,169 @@
+use std::f32;
+
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Relative error bound used to pad the far slab distance so that rounding in
+/// the slab test never culls a surface that the exact test would hit.
+const SLAB_PADDING: f32 = 1.0 + 2.0 * (3.0 * f32::EPSILON) / (1.0 - 3.0 * f32::EPSILON);
+
+/// An axis-aligned bounding box described by its minimum and maximum corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    min: Vector3,
+    max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    #[inline]
+    pub fn min(&self) -> Vector3 {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> Vector3 {
+        self.max
+    }
+
+    /// Returns the smallest box enclosing both this box and the other box.
+    #[inline]
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Returns the center point of the box.
+    #[inline]
+    pub fn centroid(&self) -> Vector3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Returns the total area of the six faces of the box.
+    #[inline]
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Returns the axis (0, 1 or 2) along which the box is longest.
+    #[inline]
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Tests whether the ray passes through the box between tmin and tmax.
+    pub fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> bool {
+        let direction = ray.direction();
+        let inv_direction = Vector3::new(
+            1.0 / direction.x(),
+            1.0 / direction.y(),
+            1.0 / direction.z(),
+        );
+        self.hit_inverse(ray.origin(), inv_direction, tmin, tmax)
+    }
+
+    /// Slab test against a ray given by its origin and the reciprocal of its
+    /// direction, allowing the reciprocal to be shared across many boxes.
+    #[inline]
+    pub fn hit_inverse(
+        &self,
+        origin: Vector3,
+        inv_direction: Vector3,
+        tmin: f32,
+        tmax: f32,
+    ) -> bool {
+        let mut t0 = tmin;
+        let mut t1 = tmax;
+        for axis in 0..3 {
+            let mut near = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let mut far = (self.max[axis] - origin[axis]) * inv_direction[axis];
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            far *= SLAB_PADDING;
+            // Written so that a NaN slab distance, from a ray lying in the
+            // plane of a face, leaves the interval unchanged.
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t0 > t1 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn hit_respects_interval_and_direction() {
+        let aabb = unit_box();
+        let ray = Ray::new(
+            Vector3::new(0.5, 0.5, -2.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        assert!(aabb.hit(&ray, 0.0, f32::MAX));
+        // The box lies between t = 2 and t = 3.
+        assert!(!aabb.hit(&ray, 0.0, 1.5));
+        assert!(!aabb.hit(&ray, 3.5, f32::MAX));
+        assert!(aabb.hit(&ray, 2.5, 2.6));
+
+        let away = Ray::new(ray.origin(), -ray.direction(), 0.0);
+        assert!(!aabb.hit(&away, 0.0, f32::MAX));
+        let beside = Ray::new(
+            Vector3::new(1.5, 0.5, -2.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        assert!(!aabb.hit(&beside, 0.0, f32::MAX));
+    }
+
+    #[test]
+    fn hit_accepts_rays_in_the_plane_of_a_face() {
+        let aabb = unit_box();
+        let ray = Ray::new(
+            Vector3::new(-1.0, 0.0, 0.5),
+            Vector3::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(aabb.hit(&ray, 0.0, f32::MAX));
+    }
+
+    #[test]
+    fn union_encloses_both_boxes() {
+        let other = Aabb::new(Vector3::new(-1.0, 0.5, 0.25), Vector3::new(0.5, 2.0, 0.75));
+        let expected = Aabb::new(Vector3::new(-1.0, 0.0, 0.0), Vector3::new(1.0, 2.0, 1.0));
+        assert_eq!(unit_box().union(&other), expected);
+        assert_eq!(other.union(&unit_box()), expected);
+        assert_eq!(unit_box().union(&unit_box()), unit_box());
+    }
+
+    #[test]
+    fn surface_area_sums_the_faces() {
+        assert_eq!(unit_box().surface_area(), 6.0);
+        let aabb = Aabb::new(Vector3::new(-1.0, 0.0, 2.0), Vector3::new(1.0, 3.0, 6.0));
+        assert_eq!(
+            aabb.surface_area(),
+            2.0 * (2.0 * 3.0 + 3.0 * 4.0 + 4.0 * 2.0)
+        );
+        let flat = Aabb::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 3.0));
+        assert_eq!(flat.surface_area(), 12.0);
+    }
+}
@@ -0,0 +1,386 @@
+use std::f32::consts::PI;
+
+use crate::image::Image;
+use crate::math::{next_random, random_unit_vector, Rng};
+use crate::vector::{Matrix4, Vector3};
+
+/// The radiance arriving along rays that leave the scene without hitting any
+/// surface: a constant color, a gradient or a panorama, turned about the
+/// scene and scaled by an intensity.
+///
+/// Backgrounds that are not black light the scene like any other light, so
+/// the renderer also samples them directly: images in proportion to their
+/// brightness, and the others evenly in every direction.
+#[derive(Debug, PartialEq)]
+pub struct Background {
+    radiance: Radiance,
+    rotation: Matrix4,
+    inverse_rotation: Matrix4,
+    intensity: f32,
+}
+
+/// How the radiance of a background varies with direction in its own frame.
+#[derive(Debug, PartialEq)]
+enum Radiance {
+    Constant(Vector3),
+    Gradient { bottom: Vector3, top: Vector3 },
+    Image(Box<EnvironmentMap>),
+}
+
+impl Background {
+    fn new(radiance: Radiance) -> Background {
+        Background {
+            radiance,
+            rotation: Matrix4::identity(),
+            inverse_rotation: Matrix4::identity(),
+            intensity: 1.0,
+        }
+    }
+
+    /// The same color in every direction. Black leaves the scene lit only by
+    /// its emissive materials.
+    pub fn constant(color: Vector3) -> Background {
+        Background::new(Radiance::Constant(color))
+    }
+
+    /// A vertical blend from the `bottom` color straight down to the `top`
+    /// color straight up.
+    pub fn gradient(bottom: Vector3, top: Vector3) -> Background {
+        Background::new(Radiance::Gradient { bottom, top })
+    }
+
+    /// A panorama surrounding the scene.
+    pub fn image(map: EnvironmentMap) -> Background {
+        Background::new(Radiance::Image(Box::new(map)))
+    }
+
+    /// Turns the background about the scene by the given rotation.
+    pub fn with_rotation(self, rotation: Matrix4) -> Background {
+        Background {
+            rotation,
+            // The inverse of a rotation is its transpose.
+            inverse_rotation: rotation.transpose(),
+            ..self
+        }
+    }
+
+    /// Scales the radiance arriving from every direction.
+    pub fn with_intensity(self, intensity: f32) -> Background {
+        Background { intensity, ..self }
+    }
+
+    /// Returns the radiance arriving from the given direction.
+    pub fn value(&self, direction: Vector3) -> Vector3 {
+        let local = self.local(direction);
+        let radiance = match &self.radiance {
+            Radiance::Constant(color) => *color,
+            Radiance::Gradient { bottom, top } => {
+                let t = 0.5 * (1.0 + local.y());
+                (1.0 - t) * *bottom + t * *top
+            }
+            Radiance::Image(map) => map.value(local),
+        };
+        self.intensity * radiance
+    }
+
+    /// Returns whether no light arrives from any direction, when there is
+    /// nothing to sample.
+    #[inline]
+    pub fn is_black(&self) -> bool {
+        self.intensity == 0.0
+            || match &self.radiance {
+                Radiance::Constant(color) => *color == Vector3::default(),
+                Radiance::Gradient { bottom, top } => {
+                    *bottom == Vector3::default() && *top == Vector3::default()
+                }
+                Radiance::Image(map) => map.is_black(),
+            }
+    }
+
+    /// Returns the solid angle density with which `random` chooses the unit
+    /// vector `direction`.
+    pub fn pdf_value(&self, direction: Vector3) -> f32 {
+        match &self.radiance {
+            // Turning the panorama leaves solid angles unchanged.
+            Radiance::Image(map) => map.pdf_value(self.local(direction)),
+            _ => 1.0 / (4.0 * PI),
+        }
+    }
+
+    /// Returns a random unit vector pointing out towards the background.
+    pub fn random(&self, rng: &mut Rng) -> Vector3 {
+        match &self.radiance {
+            Radiance::Image(map) => self.rotation.transform_vector(map.random(rng)),
+            _ => random_unit_vector(rng),
+        }
+    }
+
+    /// Returns the unit direction in the frame of the unrotated background.
+    #[inline]
+    fn local(&self, direction: Vector3) -> Vector3 {
+        self.inverse_rotation
+            .transform_vector(direction)
+            .normalized()
+    }
+}
+
+impl Default for Background {
+    /// A white to light blue sky.
+    fn default() -> Background {
+        Background::gradient(Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.5, 0.6, 1.0))
+    }
+}
+
+/// A panorama in the equirectangular projection, where the columns of the
+/// image run once around the vertical axis and the rows from straight up at
+/// the top to straight down at the bottom. The middle of the image lies
+/// along -z, with +x to its right, until the background turns it.
+///
+/// Directions are chosen with a density following the brightness of the
+/// pixels, so that the few bright pixels of a sun or a studio light, which
+/// give most of the light, are found by a few samples rather than by the
+/// rare path that happens to meet them.
+#[derive(Debug, PartialEq)]
+pub struct EnvironmentMap {
+    image: Image,
+    /// The brightness of each pixel times the solid angle it covers, which
+    /// the density of directions follows.
+    weights: Vec<f32>,
+    /// The running totals of the row weights, scaled to end at 1.
+    rows: Vec<f32>,
+    /// The running totals of the weights along each row, scaled to end at
+    /// 1, with `width + 1` entries per row.
+    columns: Vec<f32>,
+    total: f32,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image) -> EnvironmentMap {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // Rows towards the poles are squeezed into smaller solid angles.
+            let sine = (PI * (y as f32 + 0.5) / height as f32).sin();
+            for x in 0..width {
+                let luminance = image
+                    .pixel(x as u32, y as u32)
+                    .dot(Vector3::new(0.2126, 0.7152, 0.0722));
+                weights.push(luminance.max(0.0) * sine);
+            }
+        }
+
+        let mut columns = Vec::with_capacity((width + 1) * height);
+        let mut row_totals = Vec::with_capacity(height);
+        for row in weights.chunks(width) {
+            let start = columns.len();
+            columns.push(0.0);
+            let mut sum = 0.0;
+            for &weight in row {
+                sum += weight;
+                columns.push(sum);
+            }
+            normalize_cdf(&mut columns[start..]);
+            row_totals.push(sum);
+        }
+        let mut rows = Vec::with_capacity(height + 1);
+        rows.push(0.0);
+        let mut total = 0.0;
+        for &row_total in &row_totals {
+            total += row_total;
+            rows.push(total);
+        }
+        normalize_cdf(&mut rows);
+
+        EnvironmentMap {
+            image,
+            weights,
+            rows,
+            columns,
+            total,
+        }
+    }
+
+    /// Returns the radiance arriving from the given unit direction.
+    pub fn value(&self, direction: Vector3) -> Vector3 {
+        let (x, y) = self.pixel_at(direction);
+        self.image.pixel(x as u32, y as u32)
+    }
+
+    #[inline]
+    fn is_black(&self) -> bool {
+        self.total <= 0.0
+    }
+
+    fn pdf_value(&self, local: Vector3) -> f32 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        // Taken from x and z, as 1 - y² loses all precision near the poles.
+        let sine = local.x().hypot(local.z());
+        if sine == 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel_at(local);
+        let pixels = (self.image.width() * self.image.height()) as f32;
+        // The density over the image, where a pixel of weight w is chosen
+        // with probability w / total, over directions, where the image
+        // covers 2π by π radians stretched by 1 / sin θ.
+        let density = self.weights[y * self.image.width() as usize + x] / self.total * pixels;
+        density / (2.0 * PI * PI * sine)
+    }
+
+    fn random(&self, rng: &mut Rng) -> Vector3 {
+        let width = self.image.width() as usize;
+        let (y, dv) = sample_cdf(&self.rows, next_random(rng));
+        let row = &self.columns[y * (width + 1)..(y + 1) * (width + 1)];
+        let (x, du) = sample_cdf(row, next_random(rng));
+        let u = (x as f32 + du) / width as f32;
+        let v = (y as f32 + dv) / self.image.height() as f32;
+        let (phi, theta) = (2.0 * PI * (u - 0.5), PI * v);
+        Vector3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    /// Returns the column and row of the pixel seen along the unit direction
+    /// in the frame of the image.
+    fn pixel_at(&self, local: Vector3) -> (usize, usize) {
+        let u = 0.5 + local.x().atan2(-local.z()) / (2.0 * PI);
+        let v = local.y().clamp(-1.0, 1.0).acos() / PI;
+        let (width, height) = (self.image.width() as usize, self.image.height() as usize);
+        let x = ((u * width as f32) as usize).min(width - 1);
+        let y = ((v * height as f32) as usize).min(height - 1);
+        (x, y)
+    }
+}
+
+/// Scales running totals to end at 1, or spreads them evenly if they are all
+/// zero.
+fn normalize_cdf(cdf: &mut [f32]) {
+    let n = cdf.len() - 1;
+    let total = cdf[n];
+    for (i, value) in cdf.iter_mut().enumerate() {
+        *value = if total > 0.0 {
+            *value / total
+        } else {
+            i as f32 / n as f32
+        };
+    }
+}
+
+/// Returns the index of the bin of the normalized running totals that the
+/// uniform random number falls into, with how far into the bin it lies.
+fn sample_cdf(cdf: &[f32], u: f32) -> (usize, f32) {
+    let n = cdf.len() - 1;
+    let index = cdf.partition_point(|&total| total <= u).clamp(1, n) - 1;
+    let width = cdf[index + 1] - cdf[index];
+    let offset = if width > 0.0 {
+        ((u - cdf[index]) / width).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+    (index, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::seeded_rng;
+
+    /// A dim panorama with one bright pixel.
+    fn sun() -> EnvironmentMap {
+        let mut image = Image::new(16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                image.set_pixel(x, y, Vector3::new(0.1, 0.2, 0.3));
+            }
+        }
+        image.set_pixel(5, 2, Vector3::new(100.0, 90.0, 80.0));
+        EnvironmentMap::new(image)
+    }
+
+    #[test]
+    fn looks_along_minus_z_at_the_middle() {
+        let map = sun();
+        assert_eq!(map.pixel_at(Vector3::new(0.0, 0.0, -1.0)), (8, 4));
+        assert_eq!(map.pixel_at(Vector3::new(0.0, 1.0, 0.0)).1, 0);
+        assert_eq!(map.pixel_at(Vector3::new(1.0, -0.01, 0.0)), (12, 4));
+
+        // Turning the background carries the sun along with it.
+        let (phi, theta) = (2.0 * PI * (5.5 / 16.0 - 0.5), PI * 2.5 / 8.0);
+        let towards_sun = Vector3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        let rotation = Matrix4::rotation(Vector3::new(0.0, 1.0, 0.0), 90.0);
+        let turned = Background::image(sun()).with_rotation(rotation);
+        assert_eq!(map.value(towards_sun).x(), 100.0);
+        assert_eq!(turned.value(towards_sun).x(), 0.1);
+        let turned_sun = rotation.transform_vector(towards_sun);
+        assert_eq!(turned.value(turned_sun).x(), 100.0);
+    }
+
+    #[test]
+    fn samples_follow_pdf() {
+        let mut rng = seeded_rng(9);
+        let rotation = Matrix4::rotation(Vector3::new(1.0, 1.0, 0.0), 30.0);
+        let map = Background::image(sun())
+            .with_rotation(rotation)
+            .with_intensity(2.0);
+        let n = 200_000;
+        let mut sampled = Vector3::default();
+        let mut uniform = Vector3::default();
+        let mut pdf_total = 0.0;
+        for _ in 0..n {
+            let direction = map.random(&mut rng);
+            assert!((direction.length() - 1.0).abs() < 1e-4);
+            let pdf = map.pdf_value(direction);
+            assert!(pdf > 0.0, "{:?}", direction);
+            sampled = sampled + map.value(direction) / pdf;
+            let w = random_unit_vector(&mut rng);
+            uniform = uniform + map.value(w) * (4.0 * PI);
+            pdf_total += map.pdf_value(w) * 4.0 * PI;
+        }
+        // Both estimate the total light arriving, which sampling by
+        // brightness finds with far less variance.
+        let (sampled, uniform) = (sampled / n as f32, uniform / n as f32);
+        for axis in 0..3 {
+            let error = (sampled[axis] - uniform[axis]).abs() / uniform[axis];
+            assert!(error < 0.05, "{:?} {:?}", sampled, uniform);
+        }
+        assert!((pdf_total / n as f32 - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn gradients_turn_and_scale() {
+        let (bottom, top) = (Vector3::new(0.1, 0.2, 0.3), Vector3::new(1.0, 1.0, 1.0));
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let sky = Background::gradient(bottom, top);
+        assert_eq!(sky.value(up), top);
+        assert_eq!(sky.value(-up), bottom);
+        // Turned upside down, the top color lies below.
+        let flipped = Background::gradient(bottom, top)
+            .with_rotation(Matrix4::rotation(Vector3::new(1.0, 0.0, 0.0), 180.0))
+            .with_intensity(2.0);
+        assert!((flipped.value(-up) - 2.0 * top).length() < 1e-5);
+        assert!((flipped.value(up) - 2.0 * bottom).length() < 1e-5);
+        let horizon = Vector3::new(0.0, 0.0, -1.0);
+        assert!((flipped.value(horizon) - (bottom + top)).length() < 1e-5);
+
+        let gray = Background::constant(Vector3::new(1.0, 1.0, 1.0)).with_intensity(0.5);
+        assert_eq!(gray.value(up), Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn black_backgrounds_are_not_sampled() {
+        assert!(Background::constant(Vector3::default()).is_black());
+        assert!(!Background::default().is_black());
+        assert!(Background::default().with_intensity(0.0).is_black());
+        assert!(Background::image(EnvironmentMap::new(Image::new(4, 2))).is_black());
+        assert!(!Background::image(sun()).is_black());
+        assert!(Background::image(sun()).with_intensity(0.0).is_black());
+    }
+}
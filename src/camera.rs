@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use crate::math::{next_random, random_in_unit_disc};
+use crate::math::{next_random, random_in_unit_disc, Rng};
 use crate::ray::Ray;
 use crate::vector::Vector3;
 
@@ -12,6 +12,7 @@ pub struct Camera {
     vertical: Vector3,
     u: Vector3,
     v: Vector3,
+    #[allow(dead_code)]
     w: Vector3,
     lens_radius: f32,
     time0: f32,
@@ -19,6 +20,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vector3,
         look_at: Vector3,
@@ -42,23 +44,23 @@ impl Camera {
         let horizontal = 2.0 * half_width * focus_distance * u;
         let vertical = 2.0 * half_height * focus_distance * v;
         Camera {
-            origin: origin,
-            lower_left: lower_left,
-            horizontal: horizontal,
-            vertical: vertical,
-            u: u,
-            v: v,
-            w: w,
-            lens_radius: lens_radius,
-            time0: time0,
-            time1: time1,
+            origin,
+            lower_left,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius,
+            time0,
+            time1,
         }
     }
 
-    pub fn cast_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disc();
+    pub fn cast_ray(&self, s: f32, t: f32, rng: &mut Rng) -> Ray {
+        let rd = self.lens_radius * random_in_unit_disc(rng);
         let offset = self.u * rd.x() + self.v * rd.y();
-        let time = self.time0 + next_random() * (self.time1 - self.time0);
+        let time = self.time0 + next_random(rng) * (self.time1 - self.time0);
         Ray::new(
             self.origin + offset,
             self.lower_left + s * self.horizontal + t * self.vertical - self.origin - offset,
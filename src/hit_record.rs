@@ -1,24 +1,82 @@
 use crate::material::Material;
+use crate::ray::Ray;
 use crate::vector::Vector3;
 
-#[derive(Debug)]
+/// The smallest cosine at which a ray may meet a shading normal.
+const MIN_SHADING_COSINE: f32 = 0.01;
+
+/// Where a ray meets a surface. Every surface reports surface coordinates
+/// u and v for the hit, normally between 0 and 1, which materials pass to
+/// their textures.
+///
+/// Materials shade with the normal, which may differ from the geometric
+/// normal of the true surface where it is interpolated or perturbed to show
+/// detail that the geometry lacks.
+#[derive(Clone, Copy, Debug)]
 pub struct HitRecord<'a> {
     t: f32,
     point: Vector3,
     normal: Vector3,
-    material: &'a Material,
+    geometric_normal: Vector3,
+    dpdu: Vector3,
+    dpdv: Vector3,
+    u: f32,
+    v: f32,
+    material: &'a dyn Material,
 }
 
 impl<'a> HitRecord<'a> {
-    pub fn new(t: f32, point: Vector3, normal: Vector3, material: &Material) -> HitRecord {
+    pub fn new(
+        t: f32,
+        point: Vector3,
+        normal: Vector3,
+        u: f32,
+        v: f32,
+        material: &'a dyn Material,
+    ) -> HitRecord<'a> {
         HitRecord {
-            t: t,
-            point: point,
-            normal: normal,
-            material: material,
+            t,
+            point,
+            normal,
+            geometric_normal: normal,
+            dpdu: Vector3::default(),
+            dpdv: Vector3::default(),
+            u,
+            v,
+            material,
         }
     }
 
+    /// Returns the hit shaded with the unit normal in place of the geometric
+    /// one. The normal is tilted where need be so that the ray meets it from
+    /// the same side as the true surface, as light arriving from behind the
+    /// shading normal would otherwise be lost.
+    pub fn with_shading_normal(self, normal: Vector3, ray: &Ray) -> HitRecord<'a> {
+        let view = -ray.direction().normalized();
+        let side = if view.dot(self.geometric_normal) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let cosine = side * view.dot(normal);
+        let across = normal - view.dot(normal) * view;
+        let normal = if cosine >= MIN_SHADING_COSINE {
+            normal
+        } else if across.squared_norm() > 0.0 {
+            let sine = (1.0 - MIN_SHADING_COSINE * MIN_SHADING_COSINE).sqrt();
+            sine * across.normalized() + (side * MIN_SHADING_COSINE) * view
+        } else {
+            self.geometric_normal
+        };
+        HitRecord { normal, ..self }
+    }
+
+    /// Returns the hit with the rates of change of the point with the
+    /// surface coordinates u and v, which orient textures on the surface.
+    pub fn with_derivatives(self, dpdu: Vector3, dpdv: Vector3) -> HitRecord<'a> {
+        HitRecord { dpdu, dpdv, ..self }
+    }
+
     #[inline]
     pub fn t(&self) -> f32 {
         self.t
@@ -29,13 +87,54 @@ impl<'a> HitRecord<'a> {
         self.point
     }
 
+    /// Returns the unit normal that materials shade with.
     #[inline]
     pub fn normal(&self) -> Vector3 {
         self.normal
     }
 
+    /// Returns the unit normal of the true surface.
+    #[inline]
+    pub fn geometric_normal(&self) -> Vector3 {
+        self.geometric_normal
+    }
+
+    /// Returns the rate of change of the point with u, or zero if the
+    /// surface does not report it.
+    #[inline]
+    pub fn dpdu(&self) -> Vector3 {
+        self.dpdu
+    }
+
+    /// Returns the rate of change of the point with v, or zero if the
+    /// surface does not report it.
+    #[inline]
+    pub fn dpdv(&self) -> Vector3 {
+        self.dpdv
+    }
+
+    /// Returns whether the direction lies on the same side of the shading
+    /// normal as of the true surface. Light only passes between the hit and
+    /// directions where both agree, or it would leak through the surface.
+    #[inline]
+    pub fn same_side(&self, direction: Vector3) -> bool {
+        (direction.dot(self.normal) > 0.0) == (direction.dot(self.geometric_normal) > 0.0)
+    }
+
+    /// Returns the first surface coordinate of the hit point.
+    #[inline]
+    pub fn u(&self) -> f32 {
+        self.u
+    }
+
+    /// Returns the second surface coordinate of the hit point.
+    #[inline]
+    pub fn v(&self) -> f32 {
+        self.v
+    }
+
     #[inline]
-    pub fn material(&self) -> &Material {
+    pub fn material(&self) -> &'a dyn Material {
         self.material
     }
 }
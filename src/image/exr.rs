@@ -0,0 +1,136 @@
+//! OpenEXR (`.exr`) encoding.
+//!
+//! Images are written as single-part scanline files without compression,
+//! holding one 32-bit float channel for each of red, green and blue.
+
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+/// Pixel type tag of 32-bit float channels.
+const FLOAT: i32 = 2;
+
+pub fn write<W: Write>(image: &Image, mut w: W) -> Result<(), io::Error> {
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    let max_x = image.width() as i32 - 1;
+    let max_y = image.height() as i32 - 1;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    // Channels must be listed in alphabetical order.
+    let mut channels = Vec::new();
+    for name in &["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let window = box2i(0, 0, max_x, max_y);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    let mut center = Vec::new();
+    center.extend_from_slice(&0f32.to_le_bytes());
+    center.extend_from_slice(&0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &center);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    w.write_all(&header)?;
+
+    // The offset table gives the file position of each scanline chunk, which
+    // holds the row number, the data size and then each channel in turn.
+    let chunk_data = 3 * 4 * width;
+    let chunk_size = 4 + 4 + chunk_data;
+    let table_size = 8 * height;
+    let first = (header.len() + table_size) as u64;
+    for y in 0..height {
+        w.write_all(&(first + (y * chunk_size) as u64).to_le_bytes())?;
+    }
+
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for (y, row) in image.pixels().chunks(width.max(1)).enumerate() {
+        chunk.clear();
+        chunk.extend_from_slice(&(y as i32).to_le_bytes());
+        chunk.extend_from_slice(&(chunk_data as i32).to_le_bytes());
+        for channel in &[2, 1, 0] {
+            for pixel in row {
+                chunk.extend_from_slice(&pixel[*channel].to_le_bytes());
+            }
+        }
+        w.write_all(&chunk)?;
+    }
+    w.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn box2i(min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> Vec<u8> {
+    [min_x, min_y, max_x, max_y]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    #[test]
+    fn writes_scanline_chunks_at_their_offsets() {
+        let mut image = Image::new(2, 3);
+        image.set_pixel(1, 2, Vector3::new(4.0, 5.0, 6.0));
+        let mut data = Vec::new();
+        image.write_exr(&mut data).unwrap();
+        assert_eq!(&data[..4], &MAGIC);
+
+        let header_end = data.len() - 3 * (8 + 2 * 3 * 4) - 3 * 8;
+        let offset = |y: usize| {
+            let at = header_end + 8 * y;
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&data[at..at + 8]);
+            u64::from_le_bytes(bytes) as usize
+        };
+        let float = |at: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&data[at..at + 4]);
+            f32::from_le_bytes(bytes)
+        };
+        let last = offset(2);
+        assert_eq!(&data[last..last + 4], &2i32.to_le_bytes());
+        // Channels are stored blue, green, red, each for the whole row.
+        assert_eq!(float(last + 8 + 4), 6.0);
+        assert_eq!(float(last + 8 + 12), 5.0);
+        assert_eq!(float(last + 8 + 20), 4.0);
+        assert_eq!(float(offset(0) + 8 + 4), 0.0);
+    }
+}
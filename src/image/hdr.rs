@@ -0,0 +1,312 @@
+//! Radiance RGBE (`.hdr`) encoding and decoding.
+//!
+//! Each pixel is stored as three 8-bit mantissas sharing an 8-bit exponent.
+//! Scanlines are written with the run-length encoding understood by every
+//! reader of the format, which stores each of the four components of a row
+//! separately. Files are read with or without that encoding, but only in the
+//! usual orientation of rows from the top down.
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::image::Image;
+use crate::vector::Vector3;
+
+/// Scanline widths outside this range cannot be run-length encoded.
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+
+/// Longest run or literal sequence in a run-length encoded scanline.
+const MAX_RUN: usize = 127;
+
+/// Shortest repeated sequence worth encoding as a run.
+const MIN_RUN: usize = 4;
+
+/// Most pixels read from a file, which keeps a corrupt resolution from
+/// asking for more memory than any real panorama needs.
+const MAX_PIXELS: u32 = 1 << 28;
+
+pub fn write<W: Write>(image: &Image, mut w: W) -> Result<(), io::Error> {
+    let width = image.width() as usize;
+    writeln!(w, "#?RADIANCE")?;
+    writeln!(w, "FORMAT=32-bit_rle_rgbe")?;
+    writeln!(w)?;
+    writeln!(w, "-Y {} +X {}", image.height(), image.width())?;
+
+    let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+    let mut component = Vec::with_capacity(width);
+    for row in image.pixels().chunks(width.max(1)) {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(|&c| to_rgbe(c)).collect();
+        if !rle {
+            for pixel in &rgbe {
+                w.write_all(pixel)?;
+            }
+            continue;
+        }
+        w.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for index in 0..4 {
+            component.clear();
+            component.extend(rgbe.iter().map(|pixel| pixel[index]));
+            write_runs(&mut w, &component)?;
+        }
+    }
+    w.flush()
+}
+
+pub fn read<R: BufRead>(mut r: R) -> Result<Image, io::Error> {
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    // Header lines run up to a blank line, followed by the resolution.
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(invalid("missing resolution"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("unsupported pixel format, expected RGBE"));
+        }
+    }
+    line.clear();
+    r.read_line(&mut line)?;
+    let (height, width) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["-Y", height, "+X", width] => match (height.parse::<u32>(), width.parse::<u32>()) {
+            (Ok(height), Ok(width)) => match height.checked_mul(width) {
+                Some(pixels) if pixels > 0 && pixels <= MAX_PIXELS => (height, width),
+                _ => return Err(invalid("invalid resolution")),
+            },
+            _ => return Err(invalid("invalid resolution")),
+        },
+        _ => return Err(invalid("unsupported orientation, expected -Y +X")),
+    };
+
+    let mut image = Image::new(width, height);
+    let mut rgbe = vec![[0; 4]; width as usize];
+    for y in 0..height {
+        read_scanline(&mut r, &mut rgbe)?;
+        for (x, &pixel) in rgbe.iter().enumerate() {
+            image.set_pixel(x as u32, y, from_rgbe(pixel));
+        }
+    }
+    Ok(image)
+}
+
+/// Converts a linear color into shared exponent form.
+pub fn to_rgbe(color: Vector3) -> [u8; 4] {
+    let v = color.x().max(color.y()).max(color.z());
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let v = v.min(f32::MAX);
+    let mut exponent = v.log2().floor() as i32 + 1;
+    let mut scale = 256.0 / 2f32.powi(exponent);
+    if v * scale >= 256.0 {
+        exponent += 1;
+        scale *= 0.5;
+    }
+    // The largest values would need an exponent one past the 127 the byte
+    // can hold, so they saturate at the brightest mantissa instead.
+    if exponent > 127 {
+        exponent = 127;
+        scale = 256.0 / 2f32.powi(exponent);
+    }
+    let mantissa = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        mantissa(color.x()),
+        mantissa(color.y()),
+        mantissa(color.z()),
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Converts a color in shared exponent form back into linear values.
+pub fn from_rgbe(rgbe: [u8; 4]) -> Vector3 {
+    if rgbe[3] == 0 {
+        return Vector3::default();
+    }
+    let scale = 2f32.powi(i32::from(rgbe[3]) - 128 - 8);
+    Vector3::new(
+        (f32::from(rgbe[0]) + 0.5) * scale,
+        (f32::from(rgbe[1]) + 0.5) * scale,
+        (f32::from(rgbe[2]) + 0.5) * scale,
+    )
+}
+
+/// Writes one component of a scanline as a sequence of runs, each a count
+/// above 128 followed by the repeated byte, and literals, each a count up to
+/// 128 followed by that many bytes.
+fn write_runs<W: Write>(w: &mut W, data: &[u8]) -> Result<(), io::Error> {
+    let mut start = 0;
+    while start < data.len() {
+        // Find the next run long enough to be worth encoding.
+        let mut run_start = start;
+        let mut run_length = 0;
+        while run_start < data.len() {
+            run_length = 1;
+            while run_start + run_length < data.len()
+                && run_length < MAX_RUN
+                && data[run_start + run_length] == data[run_start]
+            {
+                run_length += 1;
+            }
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+        if run_start >= data.len() {
+            run_length = 0;
+        }
+
+        // Write the bytes before the run as literals.
+        while start < run_start {
+            let count = (run_start - start).min(MAX_RUN);
+            w.write_all(&[count as u8])?;
+            w.write_all(&data[start..start + count])?;
+            start += count;
+        }
+
+        if run_length > 0 {
+            w.write_all(&[128 + run_length as u8, data[run_start]])?;
+            start = run_start + run_length;
+        }
+    }
+    Ok(())
+}
+
+/// Reads one scanline, either run-length encoded or as plain pixels.
+fn read_scanline<R: Read>(r: &mut R, scanline: &mut [[u8; 4]]) -> Result<(), io::Error> {
+    let width = scanline.len();
+    let mut first = [0; 4];
+    r.read_exact(&mut first)?;
+    let encoded = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width)
+        && first[0] == 2
+        && first[1] == 2
+        && first[2] < 128;
+    if !encoded {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            r.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if (usize::from(first[2]) << 8 | usize::from(first[3])) != width {
+        return Err(invalid("scanline width does not match the image"));
+    }
+    for index in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0; 1];
+            r.read_exact(&mut count)?;
+            let count = usize::from(count[0]);
+            let run = count > 128;
+            let length = if run { count - 128 } else { count };
+            if length == 0 || x + length > width {
+                return Err(invalid("bad run in scanline"));
+            }
+            if run {
+                let mut byte = [0; 1];
+                r.read_exact(&mut byte)?;
+                for pixel in &mut scanline[x..x + length] {
+                    pixel[index] = byte[0];
+                }
+            } else {
+                let mut bytes = [0; 128];
+                r.read_exact(&mut bytes[..length])?;
+                for (pixel, &byte) in scanline[x..x + length].iter_mut().zip(&bytes[..length]) {
+                    pixel[index] = byte;
+                }
+            }
+            x += length;
+        }
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbe_round_trips_within_mantissa_precision() {
+        for &color in &[
+            Vector3::new(0.25, 0.5, 1.0),
+            Vector3::new(1000.0, 3.0, 0.001),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(12.5, 12.5, 255.9),
+        ] {
+            let decoded = from_rgbe(to_rgbe(color));
+            let max = color.x().max(color.y()).max(color.z());
+            let tolerance = max / 128.0;
+            assert!((decoded - color).length() <= tolerance * 2.0, "{:?}", color);
+        }
+        for &v in &[f32::MAX, f32::INFINITY] {
+            assert_eq!(to_rgbe(Vector3::new(v, 0.0, v)), [255, 0, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn writes_run_length_encoded_scanlines() {
+        let mut image = Image::new(10, 1);
+        image.set_pixel(9, 0, Vector3::new(1.0, 1.0, 1.0));
+        let mut data = Vec::new();
+        image.write_hdr(&mut data).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 10\n";
+        assert!(data.starts_with(header));
+        let body = &data[header.len()..];
+        // Each component is a run of nine zeros and a single literal.
+        let one = to_rgbe(Vector3::new(1.0, 1.0, 1.0));
+        let mut expected = vec![2, 2, 0, 10];
+        for &byte in &one {
+            expected.extend_from_slice(&[128 + 9, 0, 1, byte]);
+        }
+        assert_eq!(body, &expected[..]);
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        // Wide enough to be run-length encoded, with runs and literals.
+        let mut image = Image::new(40, 3);
+        for y in 0..3 {
+            for x in 0..40 {
+                let value = if x < 20 {
+                    0.5
+                } else {
+                    x as f32 * (y + 1) as f32
+                };
+                image.set_pixel(x, y, Vector3::new(value, 0.25 * value, 2.0));
+            }
+        }
+        let mut data = Vec::new();
+        image.write_hdr(&mut data).unwrap();
+        let decoded = read(data.as_slice()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (40, 3));
+        for (&read, &written) in decoded.pixels().iter().zip(image.pixels()) {
+            assert_eq!(read, from_rgbe(to_rgbe(written)));
+        }
+
+        // Narrow images are stored as plain pixels.
+        let mut data = b"#?RGBE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        data.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+        let decoded = read(data.as_slice()).unwrap();
+        assert_eq!(decoded.pixel(0, 0), from_rgbe([128, 64, 0, 129]));
+        assert_eq!(decoded.pixel(1, 0), Vector3::default());
+
+        let err = read(&b"P6\n"[..]).unwrap_err();
+        assert_eq!(err.to_string(), "not a Radiance HDR file");
+        for resolution in &["-Y 0 +X 4", "-Y 70000 +X 70000", "-Y 65536 +X 65536"] {
+            let data = format!("#?RADIANCE\n\n{}\n", resolution);
+            let err = read(data.as_bytes()).unwrap_err();
+            assert_eq!(err.to_string(), "invalid resolution");
+        }
+    }
+}
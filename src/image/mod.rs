@@ -0,0 +1,127 @@
+use png::{BitDepth, ColorType, Encoder, HasParameters};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::tonemap::ToneMap;
+use crate::vector::Vector3;
+
+mod exr;
+mod hdr;
+
+/// A framebuffer of linear, high dynamic range RGB colors. Row zero is the
+/// top of the image.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vector3>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Image {
+        let pixels = vec![Vector3::default(); (width * height) as usize];
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> Vector3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    #[inline]
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Vector3) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    /// Returns the pixels in rows from the top of the image down.
+    #[inline]
+    pub fn pixels(&self) -> &[Vector3] {
+        &self.pixels
+    }
+
+    /// Loads a Radiance HDR (`.hdr`) file, keeping its linear radiance.
+    pub fn load_hdr<T>(path: T) -> Result<Image, io::Error>
+    where
+        T: AsRef<Path>,
+    {
+        Image::read_hdr(File::open(path)?)
+    }
+
+    /// Decodes an image from Radiance HDR data.
+    pub fn read_hdr<R: Read>(r: R) -> Result<Image, io::Error> {
+        hdr::read(BufReader::new(r))
+    }
+
+    /// Saves the image in the format given by the extension of the path:
+    /// `.png` for an 8-bit display image, or `.hdr` (Radiance RGBE) and
+    /// `.exr` (OpenEXR) to keep the full linear radiance. The tone map is
+    /// only applied to display images.
+    pub fn save<T>(&self, path: T, tone_map: &ToneMap) -> Result<(), io::Error>
+    where
+        T: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") | Some("hdr") | Some("exr") => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported image format, expected .png, .hdr or .exr",
+                ));
+            }
+        }
+        let file = BufWriter::new(File::create(path)?);
+        match extension.as_deref() {
+            Some("png") => self.write_png(file, tone_map),
+            Some("hdr") => self.write_hdr(file),
+            _ => self.write_exr(file),
+        }
+    }
+
+    /// Writes the image as an 8-bit sRGB PNG, converting the linear radiance
+    /// with the tone map.
+    pub fn write_png<W: Write>(&self, w: W, tone_map: &ToneMap) -> Result<(), io::Error> {
+        let mut encoder = Encoder::new(w, self.width, self.height);
+        encoder.set(ColorType::RGB).set(BitDepth::Eight);
+
+        let mut data = Vec::with_capacity(3 * self.pixels.len());
+        for pixel in &self.pixels {
+            data.extend_from_slice(&tone_map.encode(*pixel));
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(data.as_slice())?;
+
+        Ok(())
+    }
+
+    /// Writes the image as a run-length encoded Radiance RGBE file.
+    pub fn write_hdr<W: Write>(&self, w: W) -> Result<(), io::Error> {
+        hdr::write(self, w)
+    }
+
+    /// Writes the image as an uncompressed OpenEXR file with 32-bit float
+    /// channels.
+    pub fn write_exr<W: Write>(&self, w: W) -> Result<(), io::Error> {
+        exr::write(self, w)
+    }
+}
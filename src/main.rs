@@ -1,37 +1,66 @@
 extern crate png;
 extern crate rand;
 
+use std::env;
 use std::error::Error;
-use std::f32;
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::process;
 
+mod aabb;
+mod background;
 mod camera;
 mod hit_record;
 mod image;
 mod material;
 mod math;
+mod obj;
+mod options;
 mod ray;
+mod render;
 mod scatter_record;
+mod scene;
+mod spectrum;
 mod surface;
 mod texture;
+mod tonemap;
 mod vector;
 
+pub use crate::aabb::Aabb;
+pub use crate::background::{Background, EnvironmentMap};
 pub use crate::camera::Camera;
 pub use crate::hit_record::HitRecord;
 pub use crate::image::Image;
-pub use crate::material::{Dielectric, Lambertian, Material, Metal};
-pub use crate::math::{next_random, random_in_unit_disc, random_in_unit_sphere, reflect, refract, schlick};
+pub use crate::material::{
+    BumpMap, Dielectric, DiffuseLight, Dispersion, Isotropic, Lambertian, Material, Metal,
+    Microfacet, NormalMap, Principled,
+};
+pub use crate::math::{
+    hashed_random, next_random, orthonormal_basis, pixel_rng, random_in_unit_disc,
+    random_in_unit_sphere, random_unit_vector, reflect, refract, schlick, seeded_rng, Rng,
+};
+pub use crate::obj::{ObjError, ObjModel};
+pub use crate::options::{Options, OptionsError, USAGE};
 pub use crate::ray::Ray;
+pub use crate::render::{render, Heuristic};
 pub use crate::scatter_record::ScatterRecord;
-pub use crate::surface::{MovingSphere, Sphere, Surface};
-pub use crate::texture::{CheckerTexture, ConstantTexture, Texture};
-pub use crate::vector::Vector3;
-
-const MAX_DEPTH: i32 = 50;
-
-fn build_world() -> impl Surface {
-    let mut world: Vec<Box<Surface>> = Vec::new();
+pub use crate::scene::{Scene, SceneError};
+pub use crate::spectrum::{
+    cie_xyz, rgb_to_spectrum, sample_wavelengths, spectrum_to_rgb, wavelength_pdf, MAX_WAVELENGTH,
+    MIN_WAVELENGTH,
+};
+pub use crate::surface::{
+    BoxSurface, Bvh, ConstantMedium, Instance, MeshFace, MovingSphere, Sphere, Surface,
+    Transformed, Triangle, TriangleMesh, XYRect, XZRect, YZRect,
+};
+pub use crate::texture::{
+    Addressing, CheckerTexture, ColorSpace, ConstantTexture, Filter, ImageTexture, MarbleTexture,
+    NoiseTexture, Perlin, Texture, Turbulence, WoodTexture,
+};
+pub use crate::tonemap::{srgb_decode, srgb_encode, Overflow, ToneMap, ToneOperator};
+pub use crate::vector::{Matrix4, Vector3};
+
+fn build_world(seed: u64) -> Bvh {
+    let mut rng = seeded_rng(seed);
+    let mut world: Vec<Box<dyn Surface>> = Vec::new();
     world.push(Box::new(Sphere::new(
         Vector3::new(0.0, -1000.0, 0.0),
         1000.0,
@@ -47,23 +76,24 @@ fn build_world() -> impl Surface {
     for a in -11..11 {
         for b in -11..11 {
             let center = Vector3::new(
-                a as f32 + 0.6 * next_random(),
+                a as f32 + 0.6 * next_random(&mut rng),
                 0.2,
-                b as f32 + 0.6 * next_random(),
+                b as f32 + 0.6 * next_random(&mut rng),
             );
-            if center.distance_to(deadzone1) > 0.9 && center.distance_to(deadzone2) > 0.9
+            if center.distance_to(deadzone1) > 0.9
+                && center.distance_to(deadzone2) > 0.9
                 && center.distance_to(deadzone3) > 0.9
             {
-                let chance = next_random();
+                let chance = next_random(&mut rng);
                 if chance < 0.75 {
                     let material = Lambertian::new(ConstantTexture::new(Vector3::new(
-                        next_random() * next_random(),
-                        next_random() * next_random(),
-                        next_random() * next_random(),
+                        next_random(&mut rng) * next_random(&mut rng),
+                        next_random(&mut rng) * next_random(&mut rng),
+                        next_random(&mut rng) * next_random(&mut rng),
                     )));
                     world.push(Box::new(MovingSphere::new(
-                        center + Vector3::new(0.0, next_random() * 0.1, 0.0),
-                        center + Vector3::new(0.0, next_random() * 0.1, 0.0),
+                        center + Vector3::new(0.0, next_random(&mut rng) * 0.1, 0.0),
+                        center + Vector3::new(0.0, next_random(&mut rng) * 0.1, 0.0),
                         0.0,
                         10.0,
                         0.2,
@@ -72,11 +102,11 @@ fn build_world() -> impl Surface {
                 } else if chance < 0.9 {
                     let material = Metal::new(
                         ConstantTexture::new(Vector3::new(
-                            0.5 * (1.0 + next_random()),
-                            0.5 * (1.0 + next_random()),
-                            0.5 * next_random(),
+                            0.5 * (1.0 + next_random(&mut rng)),
+                            0.5 * (1.0 + next_random(&mut rng)),
+                            0.5 * next_random(&mut rng),
                         )),
-                        0.1 * next_random(),
+                        0.1 * next_random(&mut rng),
                     );
                     world.push(Box::new(Sphere::new(center, 0.2, material)));
                 } else {
@@ -103,7 +133,7 @@ fn build_world() -> impl Surface {
         Metal::new(ConstantTexture::new(Vector3::new(0.7, 0.6, 0.5)), 0.0),
     )));
 
-    world
+    Bvh::new(world, 0.0, 1.0)
 }
 
 fn build_camera(nx: u32, ny: u32) -> Camera {
@@ -127,85 +157,35 @@ fn build_camera(nx: u32, ny: u32) -> Camera {
     )
 }
 
-fn color(world: &Surface, ray: &Ray) -> Vector3 {
-    fn color_rec(world: &Surface, ray: &Ray, depth: i32) -> Vector3 {
-        if depth >= MAX_DEPTH {
-            return Vector3::new(0.0, 0.0, 0.0);
-        }
-        if let Some(hit) = world.hit(ray, 0.01, f32::MAX) {
-            if let Some(scatter) = hit.material().scatter(ray, &hit) {
-                return scatter.attenuation() * color_rec(world, scatter.ray(), depth + 1);
-            }
-            return Vector3::new(0.0, 0.0, 0.0);
-        }
-        let unit_direction = ray.direction().normalized();
-        let t = 0.5 * (1.0 + unit_direction.y());
-        (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.6, 1.0)
-    }
-    color_rec(world, ray, 0)
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let nx = options.width();
+    let ny = options.height();
+    let scene = match options.scene() {
+        Some(path) => Scene::load(path, nx as f32 / ny as f32)
+            .map_err(|err| format!("{}: {}", path.display(), err))?,
+        None => Scene::new(build_world(options.seed()), build_camera(nx, ny)),
+    };
+    let image = render(&scene, options);
+    image
+        .save(options.output(), &options.tone_map())
+        .map_err(|err| format!("{}: {}", options.output().display(), err))?;
+    Ok(())
 }
 
-fn render(
-    world: impl Surface,
-    camera: Camera,
-    nx: u32,
-    ny: u32,
-    nsamples: u32,
-    nthreads: u32,
-) -> Image {
-    let world = Arc::new(world);
-    let camera = Arc::new(camera);
-    let image = Arc::new(Mutex::new(Image::new(nx, ny)));
-
-    let mut threads = Vec::new();
-    for threadid in 0..nthreads {
-        let world = Arc::clone(&world);
-        let camera = Arc::clone(&camera);
-        let image = Arc::clone(&image);
-        let handle = thread::spawn(move || {
-            for y in 0..ny {
-                if y % nthreads != threadid {
-                    continue;
-                }
-                for x in 0..nx {
-                    let mut c = Vector3::new(0.0, 0.0, 0.0);
-                    for _ in 0..nsamples {
-                        let u = (x as f32 + next_random()) / (nx as f32);
-                        let v = (y as f32 + next_random()) / (ny as f32);
-                        let ray = camera.cast_ray(u, v);
-                        c = c + color(world.as_ref(), &ray)
-                    }
-                    // Average the samples to determine pixel color.
-                    c = c / nsamples as f32;
-                    // Apply approximate gamma correction to the color.
-                    c = c.sqrt();
-                    // Update image with the newly calculated pixel, flipping the y
-                    // axis to match the expected coordinate system.
-                    let mut image = image.lock().unwrap();
-                    image.set_pixel(x, ny - y - 1, c);
-                }
-            }
-        });
-        threads.push(handle);
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("raytracer: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help() {
+        println!("{}", USAGE);
+        return;
     }
-
-    for thread in threads {
-        thread.join().unwrap();
+    if let Err(err) = run(&options) {
+        eprintln!("raytracer: {}", err);
+        process::exit(1);
     }
-
-    Arc::try_unwrap(image).unwrap().into_inner().unwrap()
-}
-
-fn main() -> Result<(), Box<Error>> {
-    let nx = 640;
-    let ny = 480;
-    let nsamples = 128;
-    let nthreads = 8;
-
-    let world = build_world();
-    let camera = build_camera(nx, ny);
-    let image = render(world, camera, nx, ny, nsamples, nthreads);
-    image.save("raytracer.png")?;
-
-    Ok(())
 }
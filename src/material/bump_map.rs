@@ -0,0 +1,158 @@
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::Rng;
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// The largest step in u and v taken to find the slope of the height, small
+/// next to a texel of a 1024 pixel image.
+const MAX_STEP: f32 = 1.0 / 1024.0;
+
+/// The largest distance across the surface taken to find the slope of the
+/// height, small next to the features of patterns such as noise.
+const MAX_DISTANCE: f32 = 1e-3;
+
+/// Shades another material as if its surface were raised along the normal
+/// by a height read from the red channel of a texture, giving bumps and
+/// grooves without changing its shape.
+///
+/// The normal is tilted by the slope of the height, found by comparing it
+/// with the height a short step away along u and v. Only surfaces that
+/// report how their points change with u and v can be bumped; others are
+/// shaded unchanged.
+#[derive(Debug)]
+pub struct BumpMap {
+    material: Box<dyn Material>,
+    height: Box<dyn Texture>,
+    scale: f32,
+}
+
+impl BumpMap {
+    /// Creates a bump map raising the surface by the height times the scale.
+    pub fn new(material: impl Material, height: impl Texture, scale: f32) -> BumpMap {
+        BumpMap {
+            material: Box::new(material),
+            height: Box::new(height),
+            scale,
+        }
+    }
+
+    /// Returns the hit shaded with the normal of the raised surface.
+    fn perturb<'a>(&self, ray: &Ray, hit: &HitRecord<'a>) -> HitRecord<'a> {
+        let (u, v, point, normal) = (hit.u(), hit.v(), hit.point(), hit.normal());
+        let (dpdu, dpdv) = (hit.dpdu(), hit.dpdv());
+        if dpdu.cross(dpdv).squared_norm() == 0.0 {
+            return *hit;
+        }
+        let height = |u, v, point| self.scale * self.height.value(u, v, point).x();
+        let base = height(u, v, point);
+        let du = MAX_STEP.min(MAX_DISTANCE / dpdu.length());
+        let dv = MAX_STEP.min(MAX_DISTANCE / dpdv.length());
+        let dhdu = (height(u + du, v, point + du * dpdu) - base) / du;
+        let dhdv = (height(u, v + dv, point + dv * dpdv) - base) / dv;
+        let perturbed = (dpdu + dhdu * normal).cross(dpdv + dhdv * normal);
+        // The cross product points either way depending on the handedness of
+        // u and v; the bumped normal keeps to the side of the original.
+        let perturbed = if perturbed.dot(normal) < 0.0 {
+            -perturbed
+        } else {
+            perturbed
+        };
+        if perturbed.squared_norm() > 0.0 {
+            hit.with_shading_normal(perturbed.normalized(), ray)
+        } else {
+            *hit
+        }
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let hit = self.perturb(ray, hit);
+        let scatter = self.material.scatter(ray, &hit, rng)?;
+        if hit.same_side(scatter.ray().direction()) {
+            Some(scatter)
+        } else {
+            None
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        let hit = self.perturb(ray, hit);
+        if hit.same_side(direction) {
+            self.material.eval(ray, &hit, direction)
+        } else {
+            Vector3::default()
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        self.material.pdf(ray, &self.perturb(ray, hit), direction)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        self.material.emitted(u, v, point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    /// A height rising along x by the given slope.
+    #[derive(Debug)]
+    struct Ramp(f32);
+
+    impl Texture for Ramp {
+        fn value(&self, _u: f32, _v: f32, point: Vector3) -> Vector3 {
+            let height = self.0 * point.x();
+            Vector3::new(height, height, height)
+        }
+    }
+
+    #[test]
+    fn tilts_with_the_slope_of_the_height() {
+        let material = BumpMap::new(
+            Lambertian::new(ConstantTexture::new(Vector3::new(0.5, 0.5, 0.5))),
+            Ramp(1.0),
+            0.5,
+        );
+        let hit = HitRecord::new(
+            1.0,
+            Vector3::default(),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.5,
+            0.5,
+            &material,
+        )
+        .with_derivatives(Vector3::new(3.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 3.0));
+        let ray = Ray::new(
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        // A surface rising by half a unit for every unit along x faces back
+        // along x by the same proportion.
+        let expected = Vector3::new(-0.5, 1.0, 0.0).normalized();
+        let normal = material.perturb(&ray, &hit).normal();
+        assert!((normal - expected).length() < 1e-3, "{:?}", normal);
+
+        let flat = HitRecord::new(
+            1.0,
+            Vector3::default(),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.5,
+            0.5,
+            &material,
+        );
+        assert_eq!(material.perturb(&ray, &flat).normal(), flat.normal());
+    }
+}
@@ -1,51 +1,384 @@
 use crate::hit_record::HitRecord;
+use crate::material::facing_normal;
+use crate::material::ggx::{Frame, Ggx, GlassLobe};
 use crate::material::Material;
-use crate::math::{next_random, reflect, refract, schlick};
+use crate::math::{next_random, reflect, refract, schlick, Rng};
 use crate::ray::Ray;
 use crate::scatter_record::ScatterRecord;
 use crate::vector::Vector3;
 
+/// The wavelength in nanometres of the sodium D line, at which refractive
+/// indices are usually quoted.
+const SODIUM_D_LINE: f32 = 589.3;
+
+/// How the refractive index of a dielectric varies with wavelength, which
+/// splits white light into its colors. Both formulas take wavelengths in
+/// micrometres, as tables of their coefficients do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dispersion {
+    /// Cauchy's equation `n = a + b / λ²`, a good fit for most glasses
+    /// across the visible range.
+    Cauchy { a: f32, b: f32 },
+    /// The Sellmeier equation `n² = 1 + Σ b λ² / (λ² - c)`, which is
+    /// accurate over a wider range.
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    /// Returns the refractive index at the wavelength in nanometres.
+    pub fn index(&self, wavelength: f32) -> f32 {
+        let micrometres = wavelength * 1e-3;
+        let squared = micrometres * micrometres;
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / squared,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * squared / (squared - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+/// A transparent material such as glass or water that reflects and refracts
+/// light at its surface. Rough dielectrics spread both over a GGX microfacet
+/// distribution, as frosted glass does.
+///
+/// Light travelling inside the material is absorbed following the
+/// Beer-Lambert law, tinting thick parts of colored glass more deeply than
+/// thin ones. Thin-walled dielectrics instead stand for a sheet so thin that
+/// it has no inside, such as a window pane or a soap bubble, letting light
+/// through without bending or absorbing it.
+///
+/// The refractive index of a dispersive dielectric varies with wavelength,
+/// which only spectral renders follow; RGB renders use its index at the
+/// sodium D line.
 #[derive(Debug)]
 pub struct Dielectric {
     ri: f32,
+    dispersion: Option<Dispersion>,
+    roughness: f32,
+    absorption: Vector3,
+    thin: bool,
 }
 
 impl Dielectric {
     pub fn new(ri: f32) -> Dielectric {
-        Dielectric { ri }
+        Dielectric {
+            ri,
+            dispersion: None,
+            roughness: 0.0,
+            absorption: Vector3::default(),
+            thin: false,
+        }
     }
-}
 
-impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+    /// Creates a dielectric whose refractive index varies with wavelength.
+    pub fn dispersive(dispersion: Dispersion) -> Dielectric {
+        Dielectric {
+            dispersion: Some(dispersion),
+            ..Dielectric::new(dispersion.index(SODIUM_D_LINE))
+        }
+    }
+
+    /// Sets the roughness of the surface, where 0 is a perfect mirror and 1
+    /// scatters light widely.
+    pub fn with_roughness(self, roughness: f32) -> Dielectric {
+        Dielectric { roughness, ..self }
+    }
+
+    /// Sets the attenuation coefficient of each color inside the material,
+    /// so that light travelling a distance d through it keeps the fraction
+    /// exp(-absorption * d).
+    pub fn with_absorption(self, absorption: Vector3) -> Dielectric {
+        Dielectric { absorption, ..self }
+    }
+
+    /// Makes the surface a thin wall with no inside.
+    pub fn thin_walled(self) -> Dielectric {
+        Dielectric { thin: true, ..self }
+    }
+
+    /// Returns the refractive index for the light the ray carries.
+    #[inline]
+    fn index(&self, ray: &Ray) -> f32 {
+        match (self.dispersion, ray.wavelength()) {
+            (Some(dispersion), Some(wavelength)) => dispersion.index(wavelength),
+            _ => self.ri,
+        }
+    }
+
+    /// Returns the fraction of light that survives the way along the ray to
+    /// the hit, which is absorbed only when it ran inside the material.
+    ///
+    /// The whole way from the ray's origin counts as inside, which holds for
+    /// a single closed object that no other surface sits within. A ray that
+    /// started on some other surface inside the glass, or glass nested in
+    /// another medium, is absorbed over the wrong distance.
+    fn transmittance(&self, ray: &Ray, hit: &HitRecord) -> Vector3 {
+        if self.thin || ray.direction().dot(hit.normal()) <= 0.0 {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+        let distance = hit.t() * ray.direction().length();
+        let a = self.absorption;
+        Vector3::new(
+            (-a.x() * distance).exp(),
+            (-a.y() * distance).exp(),
+            (-a.z() * distance).exp(),
+        )
+    }
+
+    /// Returns the shading frame, the view direction in it and the lobe of
+    /// a rough surface, or None if the ray arrives exactly along it.
+    fn rough(&self, ray: &Ray, hit: &HitRecord) -> Option<(Frame, Vector3, GlassLobe)> {
+        let frame = Frame::new(facing_normal(ray, hit));
+        let wo = frame.local(-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let ri = self.index(ray);
+        let entering = self.thin || ray.direction().dot(hit.normal()) < 0.0;
+        let eta = if entering { ri } else { 1.0 / ri };
+        let lobe = GlassLobe::new(Ggx::from_roughness(self.roughness), eta, self.thin);
+        Some((frame, wo, lobe))
+    }
+
+    fn scatter_smooth(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> ScatterRecord {
         let ray_direction = ray.direction();
         let hit_normal = hit.normal();
         let dot = ray_direction.dot(hit_normal);
+        let attenuation = self.transmittance(ray, hit);
+        let ri = self.index(ray);
+
+        if self.thin {
+            // Light bouncing between the two sides of the wall adds to the
+            // reflection, and the rest leaves parallel to where it came from.
+            let reflectance = schlick(dot.abs() / ray_direction.length(), ri);
+            let direction = if next_random(rng) < 2.0 * reflectance / (1.0 + reflectance) {
+                reflect(ray_direction, hit_normal)
+            } else {
+                ray_direction
+            };
+            let ray_out = Ray::new(hit.point(), direction, ray.time());
+            return ScatterRecord::specular(ray_out, attenuation);
+        }
 
         let normal_out: Vector3;
         let cosine: f32;
         let ni_over_nt: f32;
         if dot > 0.0 {
             normal_out = -hit_normal;
-            cosine = self.ri * dot / ray_direction.length();
-            ni_over_nt = self.ri;
+            cosine = ri * dot / ray_direction.length();
+            ni_over_nt = ri;
         } else {
             normal_out = hit_normal;
             cosine = -dot / ray_direction.length();
-            ni_over_nt = 1.0 / self.ri;
+            ni_over_nt = 1.0 / ri;
         }
 
         if let Some(refracted) = refract(ray_direction, normal_out, ni_over_nt) {
-            let reflect_probability = schlick(cosine, self.ri);
-            if next_random() > reflect_probability {
+            let reflect_probability = schlick(cosine, ri);
+            if next_random(rng) > reflect_probability {
                 let ray_out = Ray::new(hit.point(), refracted, ray.time());
-                let scatter = ScatterRecord::new(ray_out, Vector3::new(1.0, 1.0, 1.0));
-                return Some(scatter);
+                return ScatterRecord::specular(ray_out, attenuation);
             }
         }
 
         let ray_out = Ray::new(hit.point(), reflect(ray_direction, hit_normal), ray.time());
-        let scatter = ScatterRecord::new(ray_out, Vector3::new(1.0, 1.0, 1.0));
-        Some(scatter)
+        ScatterRecord::specular(ray_out, attenuation)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        if self.roughness <= 0.0 {
+            return Some(self.scatter_smooth(ray, hit, rng));
+        }
+        let (frame, wo, lobe) = self.rough(ray, hit)?;
+        let wi = lobe.sample(wo, rng)?;
+        let pdf = lobe.pdf(wo, wi);
+        if pdf.is_nan() || pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = self.transmittance(ray, hit) * (lobe.eval(wo, wi) / pdf);
+        let ray_out = Ray::new(hit.point(), frame.world(wi), ray.time());
+        Some(ScatterRecord::new(ray_out, attenuation, pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        if self.roughness <= 0.0 {
+            return Vector3::default();
+        }
+        match self.rough(ray, hit) {
+            Some((frame, wo, lobe)) => {
+                self.transmittance(ray, hit) * lobe.eval(wo, frame.local(direction))
+            }
+            None => Vector3::default(),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        if self.roughness <= 0.0 {
+            return 0.0;
+        }
+        match self.rough(ray, hit) {
+            Some((frame, wo, lobe)) => lobe.pdf(wo, frame.local(direction)),
+            None => 0.0,
+        }
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_support::check_sampling;
+    use crate::math::seeded_rng;
+
+    #[test]
+    fn dispersion_follows_sellmeier_equation() {
+        // Schott N-BK7 borosilicate crown glass.
+        let bk7 = Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        };
+        assert!((bk7.index(SODIUM_D_LINE) - 1.5168).abs() < 1e-4);
+        assert!(bk7.index(450.0) > bk7.index(650.0));
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+        assert!((cauchy.index(500.0) - 1.54).abs() < 1e-6);
+
+        // Red and blue light leave a smooth prism face at different angles,
+        // decided by the first wavelength a ray carries, while RGB light
+        // sees the index at the D line.
+        let mut rng = seeded_rng(4);
+        let glass = Dielectric::dispersive(bk7);
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let hit = HitRecord::new(1.0, Vector3::default(), normal, 0.0, 0.0, &glass);
+        let ray = Ray::new(
+            Vector3::new(-1.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, -1.0),
+            0.0,
+        );
+        let refracted = |ray: Ray, rng: &mut Rng| loop {
+            let direction = glass.scatter(&ray, &hit, rng).unwrap().ray().direction();
+            if direction.z() < 0.0 {
+                return direction.normalized().x();
+            }
+        };
+        let (red, blue) = (
+            refracted(
+                ray.with_wavelengths(Vector3::new(650.0, 400.0, 500.0)),
+                &mut rng,
+            ),
+            refracted(
+                ray.with_wavelengths(Vector3::new(450.0, 400.0, 500.0)),
+                &mut rng,
+            ),
+        );
+        let sine = |ri: f32| 0.5f32.sqrt() / ri;
+        assert!((red - sine(bk7.index(650.0))).abs() < 1e-5);
+        assert!((blue - sine(bk7.index(450.0))).abs() < 1e-5);
+        assert!((refracted(ray, &mut rng) - sine(1.5168)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn absorbs_light_travelling_inside() {
+        let mut rng = seeded_rng(1);
+        let glass = Dielectric::new(1.5).with_absorption(Vector3::new(0.0, 0.5, 1.0));
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let hit = HitRecord::new(2.0, Vector3::default(), normal, 0.0, 0.0, &glass);
+        let outside = Ray::new(Vector3::new(0.0, 0.0, 2.0), -normal, 0.0);
+        let inside = Ray::new(Vector3::new(0.0, 0.0, -2.0), normal, 0.0);
+        for _ in 0..10 {
+            let entering = glass.scatter(&outside, &hit, &mut rng).unwrap();
+            assert_eq!(entering.attenuation(), Vector3::new(1.0, 1.0, 1.0));
+            let leaving = glass.scatter(&inside, &hit, &mut rng).unwrap();
+            let expected = Vector3::new(1.0, (-1f32).exp(), (-2f32).exp());
+            assert!((leaving.attenuation() - expected).length() < 1e-6);
+        }
+        let window = Dielectric::new(1.5)
+            .with_absorption(Vector3::new(1.0, 1.0, 1.0))
+            .thin_walled();
+        let scatter = window.scatter(&inside, &hit, &mut rng).unwrap();
+        assert_eq!(scatter.attenuation(), Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rough_samples_match_eval_and_pdf() {
+        let mut rng = seeded_rng(8);
+        let direction = Vector3::new(1.0, -1.0, -0.3);
+        for glass in &[
+            Dielectric::new(1.5).with_roughness(0.7),
+            Dielectric::new(1.33).with_roughness(0.5).thin_walled(),
+        ] {
+            // Without absorption a glass surface loses no light besides the
+            // paths lost to the microfacet model.
+            for &sign in &[1.0, -1.0] {
+                let ray = Ray::new(-direction * sign, direction * sign, 0.0);
+                check_sampling(glass, &ray, &mut rng, 1.0, 0.03);
+            }
+        }
+    }
+
+    /// Returns the mean weights of the paths the glass reflects and
+    /// transmits for light arriving along the direction at a surface facing
+    /// +z.
+    fn split(glass: &Dielectric, direction: Vector3, rng: &mut Rng) -> (f32, f32) {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let hit = HitRecord::new(1.0, Vector3::default(), normal, 0.0, 0.0, glass);
+        let ray = Ray::new(-direction, direction, 0.0);
+        let n = 100_000;
+        let (mut reflected, mut transmitted) = (0.0, 0.0);
+        for _ in 0..n {
+            if let Some(scatter) = glass.scatter(&ray, &hit, rng) {
+                if scatter.ray().direction().z() * direction.z() < 0.0 {
+                    reflected += scatter.attenuation().x();
+                } else {
+                    transmitted += scatter.attenuation().x();
+                }
+            }
+        }
+        (reflected / n as f32, transmitted / n as f32)
+    }
+
+    #[test]
+    fn splits_light_between_reflection_and_transmission() {
+        let mut rng = seeded_rng(9);
+        let head_on = Vector3::new(0.0, 0.0, -1.0);
+        let grazing = Vector3::new(0.995, 0.0, -0.1).normalized();
+        // Past the critical angle of about 42 degrees from inside.
+        let beyond_critical = Vector3::new(0.866, 0.0, 0.5);
+
+        let glass = Dielectric::new(1.5);
+        let (reflected, transmitted) = split(&glass, head_on, &mut rng);
+        assert!((reflected - 0.04).abs() < 0.003, "{}", reflected);
+        assert!((reflected + transmitted - 1.0).abs() < 1e-6);
+        let (reflected, _) = split(&glass, grazing, &mut rng);
+        assert!(
+            (reflected - schlick(0.1, 1.5)).abs() < 0.01,
+            "{}",
+            reflected
+        );
+        assert_eq!(split(&glass, beyond_critical, &mut rng), (1.0, 0.0));
+
+        // Light bouncing inside a thin wall adds to its reflection.
+        let window = Dielectric::new(1.5).thin_walled();
+        let (reflected, _) = split(&window, head_on, &mut rng);
+        assert!((reflected - 0.08 / 1.04).abs() < 0.003, "{}", reflected);
+
+        // A slightly rough surface splits the light much as a smooth one.
+        let frosted = Dielectric::new(1.5).with_roughness(0.1);
+        let (reflected, transmitted) = split(&frosted, head_on, &mut rng);
+        assert!((reflected - 0.04).abs() < 0.005, "{}", reflected);
+        assert!((transmitted - 0.96).abs() < 0.02, "{}", transmitted);
+        let (reflected, transmitted) = split(&frosted, beyond_critical, &mut rng);
+        assert!(
+            reflected > 0.9 && transmitted < 0.02,
+            "{} {}",
+            reflected,
+            transmitted
+        );
     }
 }
@@ -0,0 +1,31 @@
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::Rng;
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// A material that emits light equally in all directions and reflects none.
+#[derive(Debug)]
+pub struct DiffuseLight {
+    emit: Box<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: impl Texture) -> DiffuseLight {
+        DiffuseLight {
+            emit: Box::new(emit),
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _rng: &mut Rng) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        self.emit.value(u, v, point)
+    }
+}
@@ -0,0 +1,354 @@
+use std::f32::consts::PI;
+
+use crate::math::{next_random, orthonormal_basis, reflect, Rng};
+use crate::vector::Vector3;
+
+/// An orthonormal shading frame around a normal, in which the normal is the
+/// z axis.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    tangent: Vector3,
+    bitangent: Vector3,
+    normal: Vector3,
+}
+
+impl Frame {
+    pub fn new(normal: Vector3) -> Frame {
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Frame {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    #[inline]
+    pub fn local(&self, v: Vector3) -> Vector3 {
+        Vector3::new(
+            v.dot(self.tangent),
+            v.dot(self.bitangent),
+            v.dot(self.normal),
+        )
+    }
+
+    #[inline]
+    pub fn world(&self, v: Vector3) -> Vector3 {
+        v.x() * self.tangent + v.y() * self.bitangent + v.z() * self.normal
+    }
+}
+
+/// The GGX or Trowbridge-Reitz distribution of microfacet normals, with the
+/// matching Smith shadowing terms. Directions are given in a local frame
+/// whose z axis is the macroscopic normal.
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Ggx {
+    /// Creates the distribution with the given widths along the x and y
+    /// axes of the local frame. Widths are kept above a small minimum, as
+    /// perfectly smooth surfaces are specular rather than microfacet.
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Ggx {
+        Ggx {
+            alpha_x: alpha_x.max(MIN_ALPHA),
+            alpha_y: alpha_y.max(MIN_ALPHA),
+        }
+    }
+
+    /// Creates the isotropic distribution for a perceptual roughness between
+    /// 0 and 1, as used by the metallic-roughness workflow, where the width
+    /// is the square of the roughness.
+    pub fn from_roughness(roughness: f32) -> Ggx {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        Ggx::new(alpha, alpha)
+    }
+
+    /// Returns the density of microfacets with the normal h per unit of
+    /// macroscopic area and solid angle.
+    #[inline]
+    pub fn d(&self, h: Vector3) -> f32 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let k = x * x + y * y + h.z() * h.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * k * k)
+    }
+
+    /// The Smith auxiliary function, the ratio of the shadowed to the
+    /// visible projected microfacet area in direction w.
+    #[inline]
+    fn lambda(&self, w: Vector3) -> f32 {
+        let z2 = w.z() * w.z();
+        if z2 == 0.0 {
+            return f32::INFINITY;
+        }
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        let tan2 = (x * x + y * y) / z2;
+        0.5 * ((1.0 + tan2).sqrt() - 1.0)
+    }
+
+    /// Returns the fraction of microfacets visible from direction w.
+    #[inline]
+    pub fn g1(&self, w: Vector3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Returns the height-correlated fraction of microfacets visible from
+    /// both directions.
+    #[inline]
+    pub fn g2(&self, wo: Vector3, wi: Vector3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from the distribution of normals visible
+    /// from direction wo, which must lie above the surface, using two
+    /// uniform random numbers.
+    pub fn sample_visible_normal(&self, wo: Vector3, u1: f32, u2: f32) -> Vector3 {
+        // Stretch the view direction into the configuration where the
+        // distribution is a hemisphere, sample the projected hemisphere there
+        // and unstretch the result (Heitz, "Sampling the GGX Distribution of
+        // Visible Normals", 2018).
+        let vh = Vector3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalized();
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0.0 {
+            Vector3::new(-vh.y(), vh.x(), 0.0) / length_squared.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let pz = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        let nh = p1 * t1 + p2 * t2 + pz * vh;
+        Vector3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(0.0),
+        )
+        .normalized()
+    }
+
+    /// Returns the density with which `sample_visible_normal` chooses the
+    /// microfacet normal h for direction wo.
+    #[inline]
+    pub fn visible_normal_pdf(&self, wo: Vector3, h: Vector3) -> f32 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+
+    /// Returns the density of reflecting wo about a visible normal sampled
+    /// from the distribution, for the direction whose half vector is h.
+    #[inline]
+    pub fn reflection_pdf(&self, wo: Vector3, h: Vector3) -> f32 {
+        // Reflecting about h turns the density of normals into one of
+        // directions through the Jacobian 1 / (4 wo.h).
+        self.visible_normal_pdf(wo, h) / (4.0 * wo.dot(h))
+    }
+}
+
+/// The smallest distribution width, below which the distribution becomes
+/// too peaked to evaluate reliably in single precision.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Returns the Schlick approximation of the Fresnel reflectance for a
+/// reflectance `f0` at normal incidence.
+#[inline]
+pub fn fresnel_schlick(f0: Vector3, cosine: f32) -> Vector3 {
+    let weight = (1.0 - cosine).clamp(0.0, 1.0).powi(5);
+    f0 + weight * (Vector3::new(1.0, 1.0, 1.0) - f0)
+}
+
+/// Returns the fraction of unpolarized light reflected by a smooth
+/// dielectric interface, for light meeting it at the given cosine to its
+/// normal and a ratio `eta` of the index on the far side to the index on the
+/// near side. Total internal reflection gives one.
+#[inline]
+pub fn fresnel_dielectric(cosine: f32, eta: f32) -> f32 {
+    let cos_i = cosine.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+/// Refracts the unit direction w, pointing away from the interface on the
+/// side of the unit normal h, into the far side, where the ratio of the
+/// indices is `eta`. Returns None for total internal reflection.
+#[inline]
+pub fn refract_through(w: Vector3, h: Vector3, eta: f32) -> Option<Vector3> {
+    let cos_i = w.dot(h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * h)
+}
+
+/// Reflection and transmission by a rough dielectric interface, in the
+/// local frame of `Ggx` with the view direction wo above the surface.
+#[derive(Clone, Copy, Debug)]
+pub struct GlassLobe {
+    ggx: Ggx,
+    eta: f32,
+    thin: bool,
+}
+
+impl GlassLobe {
+    /// Creates the lobe for an interface with the ratio `eta` of the index
+    /// below the surface to the index above it. A thin lobe stands for a
+    /// wall with two parallel interfaces close together, which lets light
+    /// through without bending it.
+    pub fn new(ggx: Ggx, eta: f32, thin: bool) -> GlassLobe {
+        GlassLobe { ggx, eta, thin }
+    }
+
+    /// Returns the chance of reflection at a microfacet met at the given
+    /// cosine. A thin wall also reflects the light bouncing back and forth
+    /// between its two sides.
+    #[inline]
+    fn reflectance(&self, cosine: f32) -> f32 {
+        let fresnel = fresnel_dielectric(cosine, self.eta);
+        if self.thin && fresnel < 1.0 {
+            2.0 * fresnel / (1.0 + fresnel)
+        } else {
+            fresnel
+        }
+    }
+
+    /// Returns the half vector of refraction between wo and wi, on the side
+    /// of wo, if wi lies on the far side of a microfacet with that normal.
+    #[inline]
+    fn refraction_half_vector(&self, wo: Vector3, wi: Vector3) -> Option<Vector3> {
+        let h = (wo + self.eta * wi).normalized();
+        let h = if h.z() < 0.0 { -h } else { h };
+        if wo.dot(h) > 0.0 && wi.dot(h) < 0.0 {
+            Some(h)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the BSDF times the cosine for the direction wi.
+    pub fn eval(&self, wo: Vector3, wi: Vector3) -> f32 {
+        if wi.z() > 0.0 {
+            let h = (wo + wi).normalized();
+            let microfacet = self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z());
+            return self.reflectance(wo.dot(h)) * microfacet;
+        }
+        if wi.z() == 0.0 {
+            return 0.0;
+        }
+        if self.thin {
+            // Light through a thin wall leaves along the mirror image of its
+            // reflection.
+            let mirrored = Vector3::new(wi.x(), wi.y(), -wi.z());
+            let h = (wo + mirrored).normalized();
+            let microfacet = self.ggx.d(h) * self.ggx.g2(wo, mirrored) / (4.0 * wo.z());
+            return (1.0 - self.reflectance(wo.dot(h))) * microfacet;
+        }
+        match self.refraction_half_vector(wo, wi) {
+            Some(h) => {
+                let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+                let denominator = (wi.dot(h) + wo.dot(h) / self.eta).powi(2);
+                self.ggx.d(h)
+                    * self.ggx.g2(wo, wi)
+                    * (1.0 - fresnel)
+                    * (wi.dot(h) * wo.dot(h) / (wo.z() * denominator)).abs()
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Returns the density of `sample` choosing the direction wi.
+    pub fn pdf(&self, wo: Vector3, wi: Vector3) -> f32 {
+        if wi.z() == 0.0 {
+            return 0.0;
+        }
+        if wi.z() > 0.0 || self.thin {
+            let reflected = Vector3::new(wi.x(), wi.y(), wi.z().abs());
+            let h = (wo + reflected).normalized();
+            let reflectance = self.reflectance(wo.dot(h));
+            let chance = if wi.z() > 0.0 {
+                reflectance
+            } else {
+                1.0 - reflectance
+            };
+            return chance * self.ggx.reflection_pdf(wo, h);
+        }
+        match self.refraction_half_vector(wo, wi) {
+            Some(h) => {
+                let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+                let denominator = (wi.dot(h) + wo.dot(h) / self.eta).powi(2);
+                (1.0 - fresnel) * self.ggx.visible_normal_pdf(wo, h) * wi.dot(h).abs() / denominator
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Samples a direction of reflection or transmission, or None if it
+    /// would leave on the wrong side of the surface.
+    pub fn sample(&self, wo: Vector3, rng: &mut Rng) -> Option<Vector3> {
+        let h = self
+            .ggx
+            .sample_visible_normal(wo, next_random(rng), next_random(rng));
+        let reflected = reflect(-wo, h);
+        if next_random(rng) < self.reflectance(wo.dot(h)) {
+            Some(reflected).filter(|wi| wi.z() > 0.0)
+        } else if self.thin {
+            Some(Vector3::new(reflected.x(), reflected.y(), -reflected.z()))
+                .filter(|wi| wi.z() < 0.0)
+        } else {
+            // Refraction must cross the surface as well as the microfacet.
+            refract_through(wo, h, self.eta).filter(|wi| wi.z() < 0.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{random_unit_vector, seeded_rng};
+
+    #[test]
+    fn visible_normals_follow_their_density() {
+        // Integrating the density of the sampled normals against the
+        // sampled normals themselves must give the same mean as drawing them.
+        let mut rng = seeded_rng(3);
+        let ggx = Ggx::new(0.3, 0.6);
+        let wo = Vector3::new(0.5, -0.2, 0.6).normalized();
+        let n = 400_000;
+        let (mut sampled, mut integrated, mut total) = (0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let h = ggx.sample_visible_normal(wo, next_random(&mut rng), next_random(&mut rng));
+            sampled += f64::from(h.x());
+            let w = random_unit_vector(&mut rng);
+            let pdf = f64::from(ggx.visible_normal_pdf(wo, w)) * 4.0 * f64::from(PI);
+            integrated += pdf * f64::from(w.x());
+            total += pdf;
+        }
+        let (sampled, integrated, total) =
+            (sampled / n as f64, integrated / n as f64, total / n as f64);
+        assert!((total - 1.0).abs() < 0.02, "{}", total);
+        assert!(
+            (sampled - integrated).abs() < 0.01,
+            "{} {}",
+            sampled,
+            integrated
+        );
+    }
+}
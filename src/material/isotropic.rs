@@ -0,0 +1,40 @@
+use std::f32::consts::PI;
+
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::{random_unit_vector, Rng};
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// The phase function of a participating medium that scatters light equally
+/// in all directions.
+#[derive(Debug)]
+pub struct Isotropic {
+    albedo: Box<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: impl Texture) -> Isotropic {
+        Isotropic {
+            albedo: Box::new(albedo),
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let ray_out = Ray::new(hit.point(), random_unit_vector(rng), ray.time());
+        let attenuation = self.albedo.value(hit.u(), hit.v(), hit.point());
+        Some(ScatterRecord::new(ray_out, attenuation, 1.0 / (4.0 * PI)))
+    }
+
+    fn eval(&self, _ray: &Ray, hit: &HitRecord, _direction: Vector3) -> Vector3 {
+        self.albedo.value(hit.u(), hit.v(), hit.point()) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vector3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
@@ -1,13 +1,18 @@
+use std::f32::consts::FRAC_1_PI;
+
 use crate::hit_record::HitRecord;
-use crate::material::Material;
-use crate::math::random_in_unit_sphere;
+use crate::material::{facing_normal, Material};
+use crate::math::{random_unit_vector, Rng};
 use crate::ray::Ray;
 use crate::scatter_record::ScatterRecord;
 use crate::texture::Texture;
+use crate::vector::Vector3;
 
+/// A perfectly diffuse material, scattering with a cosine weighted
+/// distribution about the normal on the side the light arrives from.
 #[derive(Debug)]
 pub struct Lambertian {
-    albedo: Box<Texture>,
+    albedo: Box<dyn Texture>,
 }
 
 impl Lambertian {
@@ -19,13 +24,27 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
-        let ray_out = Ray::new(
-            hit.point(),
-            hit.normal() + random_in_unit_sphere(),
-            ray.time(),
-        );
-        let scatter = ScatterRecord::new(ray_out, self.albedo.value(0.0, 0.0, hit.point()));
-        Some(scatter)
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let normal = facing_normal(ray, hit);
+        // Offsetting a point on the unit sphere by the normal gives directions
+        // with a density proportional to the cosine.
+        let mut direction = (normal + random_unit_vector(rng)).normalized();
+        let mut cosine = direction.dot(normal);
+        if cosine.is_nan() || cosine <= 0.0 {
+            direction = normal;
+            cosine = 1.0;
+        }
+        let ray_out = Ray::new(hit.point(), direction, ray.time());
+        let attenuation = self.albedo.value(hit.u(), hit.v(), hit.point());
+        Some(ScatterRecord::new(ray_out, attenuation, cosine * FRAC_1_PI))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        let cosine = direction.dot(facing_normal(ray, hit)).max(0.0);
+        self.albedo.value(hit.u(), hit.v(), hit.point()) * (cosine * FRAC_1_PI)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        direction.dot(facing_normal(ray, hit)).max(0.0) * FRAC_1_PI
     }
 }
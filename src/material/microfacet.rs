@@ -0,0 +1,257 @@
+use std::f32::consts::FRAC_1_PI;
+
+use crate::hit_record::HitRecord;
+use crate::material::facing_normal;
+use crate::material::ggx::{fresnel_schlick, Frame, Ggx};
+use crate::material::Material;
+use crate::math::{average, next_random, random_unit_vector, reflect, Rng};
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Reflectance at normal incidence of the dielectrics the metallic-roughness
+/// workflow assumes for non-metals.
+const DIELECTRIC_F0: f32 = 0.04;
+
+/// A physically based material following the metallic-roughness workflow
+/// used by glTF, Blender and Substance. A GGX microfacet specular lobe with
+/// Smith shadowing and Schlick Fresnel sits over a diffuse base, which fades
+/// out as the material becomes metallic, when the base color instead tints
+/// the specular reflection.
+///
+/// Metallic and roughness are read from the red channel of their textures
+/// and clamped to lie between 0 and 1.
+#[derive(Debug)]
+pub struct Microfacet {
+    base_color: Box<dyn Texture>,
+    metallic: Box<dyn Texture>,
+    roughness: Box<dyn Texture>,
+}
+
+impl Microfacet {
+    pub fn new(
+        base_color: impl Texture,
+        metallic: impl Texture,
+        roughness: impl Texture,
+    ) -> Microfacet {
+        Microfacet {
+            base_color: Box::new(base_color),
+            metallic: Box::new(metallic),
+            roughness: Box::new(roughness),
+        }
+    }
+
+    /// Looks up the parameters at the hit, returning None if the ray arrives
+    /// exactly along the surface.
+    fn shading(&self, ray: &Ray, hit: &HitRecord) -> Option<Shading> {
+        let frame = Frame::new(facing_normal(ray, hit));
+        let wo = frame.local(-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let (u, v, point) = (hit.u(), hit.v(), hit.point());
+        let base_color = self.base_color.value(u, v, point);
+        let metallic = self.metallic.value(u, v, point).x().clamp(0.0, 1.0);
+        let roughness = self.roughness.value(u, v, point).x();
+        let dielectric = Vector3::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        let f0 = (1.0 - metallic) * dielectric + metallic * base_color;
+        let diffuse = (1.0 - metallic) * base_color;
+
+        // Choose between the lobes in proportion to their estimated
+        // reflectance towards the viewer.
+        let specular_weight = average(fresnel_schlick(f0, wo.z()));
+        let diffuse_weight = average(diffuse) * (1.0 - specular_weight);
+        let total = specular_weight + diffuse_weight;
+        let specular_probability = if total > 0.0 {
+            specular_weight / total
+        } else {
+            1.0
+        };
+        Some(Shading {
+            frame,
+            wo,
+            ggx: Ggx::from_roughness(roughness),
+            f0,
+            diffuse,
+            specular_probability,
+        })
+    }
+}
+
+/// The parameters of the material at a hit, with the view direction in the
+/// shading frame.
+struct Shading {
+    frame: Frame,
+    wo: Vector3,
+    ggx: Ggx,
+    f0: Vector3,
+    diffuse: Vector3,
+    specular_probability: f32,
+}
+
+impl Shading {
+    /// Returns the specular and diffuse reflection, weighted by the cosine,
+    /// for the local direction wi.
+    fn eval(&self, wi: Vector3) -> Vector3 {
+        if wi.z() <= 0.0 {
+            return Vector3::default();
+        }
+        let h = (self.wo + wi).normalized();
+        let fresnel = fresnel_schlick(self.f0, wi.dot(h));
+        let specular =
+            fresnel * (self.ggx.d(h) * self.ggx.g2(self.wo, wi) / (4.0 * self.wo.z() * wi.z()));
+        let diffuse = (Vector3::new(1.0, 1.0, 1.0) - fresnel) * self.diffuse * FRAC_1_PI;
+        (specular + diffuse) * wi.z()
+    }
+
+    /// Returns the density of `sample` choosing the local direction wi.
+    fn pdf(&self, wi: Vector3) -> f32 {
+        if wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = (self.wo + wi).normalized();
+        let specular = self.ggx.reflection_pdf(self.wo, h);
+        let diffuse = wi.z() * FRAC_1_PI;
+        self.specular_probability * specular + (1.0 - self.specular_probability) * diffuse
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Vector3 {
+        if next_random(rng) < self.specular_probability {
+            let h = self
+                .ggx
+                .sample_visible_normal(self.wo, next_random(rng), next_random(rng));
+            reflect(-self.wo, h)
+        } else {
+            (Vector3::new(0.0, 0.0, 1.0) + random_unit_vector(rng)).normalized()
+        }
+    }
+}
+
+impl Material for Microfacet {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let shading = self.shading(ray, hit)?;
+        let wi = shading.sample(rng);
+        let pdf = shading.pdf(wi);
+        // Directions below the surface are lost, as light scattering more
+        // than once between microfacets is not modelled.
+        if wi.z() <= 0.0 || pdf.is_nan() || pdf <= 0.0 {
+            return None;
+        }
+        let direction = shading.frame.world(wi);
+        let ray_out = Ray::new(hit.point(), direction, ray.time());
+        Some(ScatterRecord::new(ray_out, shading.eval(wi) / pdf, pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        match self.shading(ray, hit) {
+            Some(shading) => shading.eval(shading.frame.local(direction)),
+            None => Vector3::default(),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        match self.shading(ray, hit) {
+            Some(shading) => shading.pdf(shading.frame.local(direction)),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_support::{check_sampling, eval_between};
+    use crate::math::seeded_rng;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn samples_match_eval_and_pdf() {
+        let mut rng = seeded_rng(5);
+        let ray = Ray::new(
+            Vector3::new(-1.0, 1.0, 0.3),
+            Vector3::new(1.0, -1.0, -0.3),
+            0.0,
+        );
+        for &(metallic, roughness) in &[(0.0, 0.5), (1.0, 0.3), (0.5, 0.8), (1.0, 0.05)] {
+            let material = Microfacet::new(
+                ConstantTexture::gray(0.9),
+                ConstantTexture::gray(metallic),
+                ConstantTexture::gray(roughness),
+            );
+            check_sampling(&material, &ray, &mut rng, 1.0, 0.02);
+        }
+    }
+
+    /// Returns the reflectance of the material for light mirrored about the
+    /// normal at the given cosine, by dividing out the microfacet term.
+    fn mirror_reflectance(material: &Microfacet, roughness: f32, cosine: f32) -> Vector3 {
+        let sine = (1.0 - cosine * cosine).sqrt();
+        let wo = Vector3::new(sine, cosine, 0.0);
+        let wi = Vector3::new(-sine, cosine, 0.0);
+        let ggx = Ggx::from_roughness(roughness);
+        let (local_wo, local_wi) = (
+            Vector3::new(sine, 0.0, cosine),
+            Vector3::new(-sine, 0.0, cosine),
+        );
+        let z = Vector3::new(0.0, 0.0, 1.0);
+        let microfacet = ggx.d(z) * ggx.g2(local_wo, local_wi) / (4.0 * cosine);
+        eval_between(material, wo, wi) / microfacet
+    }
+
+    #[test]
+    fn reflectance_rises_towards_white_at_grazing_angles() {
+        let black = ConstantTexture::gray(0.0);
+        let dielectric = Microfacet::new(
+            black,
+            ConstantTexture::gray(0.0),
+            ConstantTexture::gray(0.3),
+        );
+        let head_on = mirror_reflectance(&dielectric, 0.3, 1.0);
+        assert!((head_on - Vector3::new(0.04, 0.04, 0.04)).length() < 1e-4);
+        let grazing = mirror_reflectance(&dielectric, 0.3, 0.05);
+        assert!(grazing.x() > 0.75 && grazing.x() < 1.0, "{:?}", grazing);
+
+        // A metal reflects its base color head on, and its tint fades at
+        // grazing angles.
+        let gold = Vector3::new(1.0, 0.8, 0.3);
+        let metal = Microfacet::new(
+            ConstantTexture::new(gold),
+            ConstantTexture::gray(1.0),
+            ConstantTexture::gray(0.3),
+        );
+        assert!((mirror_reflectance(&metal, 0.3, 1.0) - gold).length() < 1e-4);
+        let grazing = mirror_reflectance(&metal, 0.3, 0.05);
+        assert!(grazing.z() > 0.8, "{:?}", grazing);
+    }
+
+    #[test]
+    fn metallic_blends_diffuse_into_tinted_specular() {
+        // Seen and lit along the normal, the diffuse lobe reflects what the
+        // specular lobe lets through of the base color over pi.
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let base_color = Vector3::new(0.8, 0.4, 0.1);
+        let eval = |metallic: f32| {
+            let material = Microfacet::new(
+                ConstantTexture::new(base_color),
+                ConstantTexture::gray(metallic),
+                ConstantTexture::gray(0.5),
+            );
+            eval_between(&material, up, up)
+        };
+        let z = Vector3::new(0.0, 0.0, 1.0);
+        let ggx = Ggx::from_roughness(0.5);
+        let microfacet = ggx.d(z) * ggx.g2(z, z) / 4.0;
+        let close = |a: Vector3, b: Vector3| (a - b).length() < 1e-4 * b.length().max(1.0);
+
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let dielectric = 0.04 * microfacet * white + 0.96 * FRAC_1_PI * base_color;
+        assert!(close(eval(0.0), dielectric));
+        assert!(close(eval(1.0), base_color * microfacet));
+        // Halfway, the reflectance is the mean of the two, and the diffuse
+        // lobe keeps half the base color minus what that reflects.
+        let f0 = 0.5 * (0.04 * white + base_color);
+        let halfway = f0 * microfacet + (white - f0) * base_color * (0.5 * FRAC_1_PI);
+        assert!(close(eval(0.5), halfway));
+    }
+}
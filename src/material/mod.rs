@@ -1,17 +1,138 @@
 use std::fmt::Debug;
 
 use crate::hit_record::HitRecord;
+use crate::math::Rng;
 use crate::ray::Ray;
 use crate::scatter_record::ScatterRecord;
+use crate::vector::Vector3;
 
+mod bump_map;
 mod dielectric;
+mod diffuse_light;
+mod ggx;
+mod isotropic;
 mod lambertian;
 mod metal;
+mod microfacet;
+mod normal_map;
+mod principled;
+#[cfg(test)]
+mod test_support;
 
-pub use self::dielectric::Dielectric;
+pub use self::bump_map::BumpMap;
+pub use self::dielectric::{Dielectric, Dispersion};
+pub use self::diffuse_light::DiffuseLight;
+pub use self::isotropic::Isotropic;
 pub use self::lambertian::Lambertian;
 pub use self::metal::Metal;
+pub use self::microfacet::Microfacet;
+pub use self::normal_map::NormalMap;
+pub use self::principled::Principled;
 
 pub trait Material: Debug + Sync + Send + 'static {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord>;
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord>;
+
+    /// Returns the BSDF times the cosine term for light arriving from the
+    /// unit vector `direction` and leaving back along `ray`. Materials that
+    /// only scatter into specular directions return black.
+    fn eval(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vector3) -> Vector3 {
+        Vector3::default()
+    }
+
+    /// Returns the solid angle density with which `scatter` chooses the unit
+    /// vector `direction`, or zero for materials that only scatter into
+    /// specular directions.
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vector3) -> f32 {
+        0.0
+    }
+
+    /// Returns whether the direction the material scatters light into
+    /// depends on its wavelength, so that a ray of several wavelengths can
+    /// only carry the first of them on.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// Returns the light emitted from the surface at the given point, which
+    /// is black for materials that are not light sources.
+    fn emitted(&self, _u: f32, _v: f32, _point: Vector3) -> Vector3 {
+        Vector3::default()
+    }
+}
+
+/// Returns the normal of the hit facing back towards the origin of the ray.
+/// Every material shades about this normal, so that both sides of a surface
+/// reflect light alike and a surface seen from behind is not black.
+#[inline]
+fn facing_normal(ray: &Ray, hit: &HitRecord) -> Vector3 {
+    if ray.direction().dot(hit.normal()) > 0.0 {
+        -hit.normal()
+    } else {
+        hit.normal()
+    }
+}
+
+impl Material for Box<dyn Material> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        self.as_ref().scatter(ray, hit, rng)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        self.as_ref().eval(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        self.as_ref().pdf(ray, hit, direction)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.as_ref().is_dispersive()
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        self.as_ref().emitted(u, v, point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_support::eval_between;
+    use crate::math::seeded_rng;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn both_sides_of_a_surface_reflect_alike() {
+        let mut rng = seeded_rng(2);
+        let materials: Vec<Box<dyn Material>> = vec![
+            Box::new(Lambertian::new(ConstantTexture::gray(0.5))),
+            Box::new(Microfacet::new(
+                ConstantTexture::gray(0.5),
+                ConstantTexture::gray(0.0),
+                ConstantTexture::gray(0.4),
+            )),
+            Box::new(Principled::new(ConstantTexture::gray(0.5))),
+        ];
+        let front = |v: Vector3| Vector3::new(v.x(), -v.y(), v.z());
+        let wo = Vector3::new(0.3, -0.8, 0.2).normalized();
+        let wi = Vector3::new(-0.5, -0.6, 0.1).normalized();
+        for material in &materials {
+            let behind = eval_between(material.as_ref(), wo, wi);
+            assert!(behind.x() > 0.0, "{:?}", material);
+            assert_eq!(
+                behind,
+                eval_between(material.as_ref(), front(wo), front(wi))
+            );
+
+            // Light seen from behind also scatters back out of that side.
+            let normal = Vector3::new(0.0, 1.0, 0.0);
+            let hit = HitRecord::new(1.0, Vector3::default(), normal, 0.0, 0.0, material);
+            let ray = Ray::new(wo, -wo, 0.0);
+            for _ in 0..100 {
+                if let Some(scatter) = material.scatter(&ray, &hit, &mut rng) {
+                    assert!(scatter.ray().direction().y() < 0.0, "{:?}", material);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,164 @@
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::{orthonormal_basis, Rng};
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Shades another material with normals read from a tangent space normal
+/// map, adding the look of fine detail to a surface without changing its
+/// shape.
+///
+/// Each color of the texture gives a normal with the red, green and blue
+/// channels mapped from 0 to 1 onto -1 to 1 along the directions in which u
+/// and v grow across the surface and the normal of the surface itself, the
+/// convention of OpenGL and most tools that bake normal maps. Images of
+/// normals hold directions rather than colors, so they must be loaded
+/// without sRGB decoding.
+#[derive(Debug)]
+pub struct NormalMap {
+    material: Box<dyn Material>,
+    normals: Box<dyn Texture>,
+    strength: f32,
+}
+
+impl NormalMap {
+    pub fn new(material: impl Material, normals: impl Texture) -> NormalMap {
+        NormalMap {
+            material: Box::new(material),
+            normals: Box::new(normals),
+            strength: 1.0,
+        }
+    }
+
+    /// Scales how far the normals tilt from the surface normal, flattening
+    /// the detail below 1 and exaggerating it above.
+    pub fn with_strength(self, strength: f32) -> NormalMap {
+        NormalMap { strength, ..self }
+    }
+
+    /// Returns the hit shaded with the normal from the map.
+    fn perturb<'a>(&self, ray: &Ray, hit: &HitRecord<'a>) -> HitRecord<'a> {
+        let normal = hit.normal();
+        let tangent = hit.dpdu() - hit.dpdu().dot(normal) * normal;
+        let (tangent, bitangent) = if tangent.squared_norm() > 0.0 {
+            let tangent = tangent.normalized();
+            let bitangent = normal.cross(tangent);
+            // Textures mirrored across the surface have v growing the
+            // other way round from u.
+            if bitangent.dot(hit.dpdv()) < 0.0 {
+                (tangent, -bitangent)
+            } else {
+                (tangent, bitangent)
+            }
+        } else {
+            orthonormal_basis(normal)
+        };
+        let color = self.normals.value(hit.u(), hit.v(), hit.point());
+        let local = 2.0 * color - Vector3::new(1.0, 1.0, 1.0);
+        let perturbed = self.strength * local.x() * tangent
+            + self.strength * local.y() * bitangent
+            + local.z() * normal;
+        if perturbed.squared_norm() > 0.0 {
+            hit.with_shading_normal(perturbed.normalized(), ray)
+        } else {
+            *hit
+        }
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let hit = self.perturb(ray, hit);
+        let scatter = self.material.scatter(ray, &hit, rng)?;
+        if hit.same_side(scatter.ray().direction()) {
+            Some(scatter)
+        } else {
+            None
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        let hit = self.perturb(ray, hit);
+        if hit.same_side(direction) {
+            self.material.eval(ray, &hit, direction)
+        } else {
+            Vector3::default()
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        self.material.pdf(ray, &self.perturb(ray, hit), direction)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        self.material.emitted(u, v, point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::math::seeded_rng;
+    use crate::texture::ConstantTexture;
+
+    fn hit(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord::new(
+            1.0,
+            Vector3::default(),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.5,
+            0.5,
+            material,
+        )
+        .with_derivatives(Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -2.0))
+    }
+
+    #[test]
+    fn flat_map_keeps_the_normal() {
+        let material = NormalMap::new(
+            Lambertian::new(ConstantTexture::new(Vector3::new(0.5, 0.5, 0.5))),
+            ConstantTexture::new(Vector3::new(0.5, 0.5, 1.0)),
+        );
+        let ray = Ray::new(
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let hit = hit(&material);
+        let perturbed = material.perturb(&ray, &hit);
+        assert!((perturbed.normal() - hit.normal()).length() < 1e-6);
+    }
+
+    #[test]
+    fn tilts_towards_tangent_and_never_leaks() {
+        let mut rng = seeded_rng(3);
+        // A normal leaning far towards u, seen from the side it leans away
+        // from.
+        let material = NormalMap::new(
+            Lambertian::new(ConstantTexture::new(Vector3::new(0.5, 0.5, 0.5))),
+            ConstantTexture::new(Vector3::new(0.95, 0.5, 0.6)),
+        );
+        let ray = Ray::new(
+            Vector3::new(-1.0, 0.2, 0.0),
+            Vector3::new(1.0, -0.2, 0.0),
+            0.0,
+        );
+        let hit = hit(&material);
+        let normal = material.perturb(&ray, &hit).normal();
+        let view = -ray.direction().normalized();
+        assert!(normal.x() > 0.0 && view.dot(normal) > 0.0);
+        assert!((normal.length() - 1.0).abs() < 1e-5);
+        for _ in 0..10_000 {
+            if let Some(scatter) = material.scatter(&ray, &hit, &mut rng) {
+                assert!(scatter.ray().direction().y() > 0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use std::f32::consts::FRAC_1_PI;
+
+use crate::hit_record::HitRecord;
+use crate::material::facing_normal;
+use crate::material::ggx::{fresnel_schlick, Frame, Ggx, GlassLobe};
+use crate::material::Material;
+use crate::math::{average, next_random, random_unit_vector, reflect, Rng};
+use crate::ray::Ray;
+use crate::scatter_record::ScatterRecord;
+use crate::texture::{ConstantTexture, Texture};
+use crate::vector::Vector3;
+
+/// Reflectance at normal incidence of the clearcoat layer, a dielectric with
+/// an index of refraction of 1.5.
+const CLEARCOAT_F0: f32 = 0.04;
+
+/// A material in the style of the Disney principled BSDF, covering plastics,
+/// metals, coated surfaces, fabrics and glass with one set of parameters.
+///
+/// It layers a clearcoat over a blend of three bases chosen by `metallic`
+/// and `transmission`: a diffuse base with a subsurface approximation, sheen
+/// and a specular reflection; a metal tinted by the base color; and a rough
+/// glass. Apart from the base color, every parameter lies between 0 and 1
+/// and is read from the red channel of its texture.
+#[derive(Debug)]
+pub struct Principled {
+    base_color: Box<dyn Texture>,
+    subsurface: Box<dyn Texture>,
+    metallic: Box<dyn Texture>,
+    specular: Box<dyn Texture>,
+    roughness: Box<dyn Texture>,
+    sheen: Box<dyn Texture>,
+    sheen_tint: Box<dyn Texture>,
+    clearcoat: Box<dyn Texture>,
+    clearcoat_roughness: Box<dyn Texture>,
+    transmission: Box<dyn Texture>,
+    ior: f32,
+}
+
+impl Principled {
+    /// Creates a rough, fully diffuse dielectric of the given base color,
+    /// with a specular of 0.5 and a glass index of refraction of 1.5.
+    pub fn new(base_color: impl Texture) -> Principled {
+        Principled {
+            base_color: Box::new(base_color),
+            subsurface: Box::new(ConstantTexture::gray(0.0)),
+            metallic: Box::new(ConstantTexture::gray(0.0)),
+            specular: Box::new(ConstantTexture::gray(0.5)),
+            roughness: Box::new(ConstantTexture::gray(0.5)),
+            sheen: Box::new(ConstantTexture::gray(0.0)),
+            sheen_tint: Box::new(ConstantTexture::gray(0.5)),
+            clearcoat: Box::new(ConstantTexture::gray(0.0)),
+            clearcoat_roughness: Box::new(ConstantTexture::gray(0.03)),
+            transmission: Box::new(ConstantTexture::gray(0.0)),
+            ior: 1.5,
+        }
+    }
+
+    /// Blends the diffuse base towards a flatter look imitating light
+    /// scattered beneath the surface.
+    pub fn with_subsurface(self, subsurface: impl Texture) -> Principled {
+        Principled {
+            subsurface: Box::new(subsurface),
+            ..self
+        }
+    }
+
+    /// Blends from a dielectric at 0 to a metal at 1.
+    pub fn with_metallic(self, metallic: impl Texture) -> Principled {
+        Principled {
+            metallic: Box::new(metallic),
+            ..self
+        }
+    }
+
+    /// Sets the reflectance at normal incidence of dielectrics, where 1 means
+    /// 8%, so that the default of 0.5 matches an index of refraction of 1.5.
+    pub fn with_specular(self, specular: impl Texture) -> Principled {
+        Principled {
+            specular: Box::new(specular),
+            ..self
+        }
+    }
+
+    /// Sets the perceptual roughness of the diffuse, specular and glass
+    /// lobes.
+    pub fn with_roughness(self, roughness: impl Texture) -> Principled {
+        Principled {
+            roughness: Box::new(roughness),
+            ..self
+        }
+    }
+
+    /// Adds the soft reflection at grazing angles seen on cloth.
+    pub fn with_sheen(self, sheen: impl Texture) -> Principled {
+        Principled {
+            sheen: Box::new(sheen),
+            ..self
+        }
+    }
+
+    /// Blends the sheen from white at 0 to the hue of the base color at 1.
+    pub fn with_sheen_tint(self, sheen_tint: impl Texture) -> Principled {
+        Principled {
+            sheen_tint: Box::new(sheen_tint),
+            ..self
+        }
+    }
+
+    /// Sets the strength of a clear varnish layer over the material.
+    pub fn with_clearcoat(self, clearcoat: impl Texture) -> Principled {
+        Principled {
+            clearcoat: Box::new(clearcoat),
+            ..self
+        }
+    }
+
+    /// Sets the perceptual roughness of the clearcoat.
+    pub fn with_clearcoat_roughness(self, clearcoat_roughness: impl Texture) -> Principled {
+        Principled {
+            clearcoat_roughness: Box::new(clearcoat_roughness),
+            ..self
+        }
+    }
+
+    /// Blends the dielectric from opaque at 0 to glass at 1, which lets light
+    /// through tinted by the base color.
+    pub fn with_transmission(self, transmission: impl Texture) -> Principled {
+        Principled {
+            transmission: Box::new(transmission),
+            ..self
+        }
+    }
+
+    /// Sets the index of refraction of the glass.
+    pub fn with_ior(self, ior: f32) -> Principled {
+        Principled { ior, ..self }
+    }
+
+    /// Weighs the lobes for the textures at the hit. Rays grazing the
+    /// surface, and materials reflecting nothing, have no lobes to sample.
+    fn shading(&self, ray: &Ray, hit: &HitRecord) -> Option<Shading> {
+        let frame = Frame::new(facing_normal(ray, hit));
+        let wo = frame.local(-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let (u, v, point) = (hit.u(), hit.v(), hit.point());
+        let scalar = |texture: &dyn Texture| texture.value(u, v, point).x().clamp(0.0, 1.0);
+        let base_color = self.base_color.value(u, v, point);
+        let metallic = scalar(self.metallic.as_ref());
+        let transmission = scalar(self.transmission.as_ref());
+        let roughness = scalar(self.roughness.as_ref());
+        let clearcoat = scalar(self.clearcoat.as_ref());
+
+        let luminance = base_color.dot(Vector3::new(0.2126, 0.7152, 0.0722));
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            Vector3::new(1.0, 1.0, 1.0)
+        };
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let sheen_tint = scalar(self.sheen_tint.as_ref());
+        let sheen = scalar(self.sheen.as_ref()) * ((1.0 - sheen_tint) * white + sheen_tint * tint);
+        let dielectric_f0 = 0.08 * scalar(self.specular.as_ref());
+        let specular_f0 = (1.0 - metallic) * dielectric_f0 * white + metallic * base_color;
+
+        // The glass is entered when the ray meets the outside of the surface.
+        let eta = if ray.direction().dot(hit.normal()) < 0.0 {
+            self.ior
+        } else {
+            1.0 / self.ior
+        };
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let glass_weight = (1.0 - metallic) * transmission;
+        let coat_fresnel = clearcoat * fresnel_schlick(CLEARCOAT_F0 * white, wo.z()).x();
+        let base_weight = 1.0 - coat_fresnel;
+
+        // Choose a lobe in proportion to an estimate of the light it
+        // reflects towards the viewer. The density of every direction sums
+        // over all lobes, so any choice leaves the estimate unbiased.
+        let weights = [
+            base_weight * diffuse_weight * average(base_color + sheen),
+            base_weight * (1.0 - glass_weight) * average(fresnel_schlick(specular_f0, wo.z())),
+            base_weight * glass_weight,
+            coat_fresnel,
+        ];
+        let total: f32 = weights.iter().sum();
+        if total.is_nan() || total <= 0.0 {
+            return None;
+        }
+        let probabilities = [
+            weights[0] / total,
+            weights[1] / total,
+            weights[2] / total,
+            weights[3] / total,
+        ];
+        let ggx = Ggx::from_roughness(roughness);
+        Some(Shading {
+            frame,
+            wo,
+            base_color,
+            subsurface: scalar(self.subsurface.as_ref()),
+            roughness,
+            sheen,
+            specular_f0,
+            diffuse_weight,
+            glass_weight,
+            base_weight,
+            clearcoat,
+            ggx,
+            coat_ggx: Ggx::from_roughness(scalar(self.clearcoat_roughness.as_ref())),
+            glass: GlassLobe::new(ggx, eta, false),
+            probabilities,
+        })
+    }
+}
+
+/// The Schlick weight, growing from 0 at normal incidence to 1 at grazing
+/// angles.
+#[inline]
+fn schlick_weight(cosine: f32) -> f32 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+/// The lobes of the material at a hit, with the view direction in the
+/// shading frame.
+struct Shading {
+    frame: Frame,
+    wo: Vector3,
+    base_color: Vector3,
+    subsurface: f32,
+    roughness: f32,
+    sheen: Vector3,
+    specular_f0: Vector3,
+    diffuse_weight: f32,
+    glass_weight: f32,
+    base_weight: f32,
+    clearcoat: f32,
+    ggx: Ggx,
+    coat_ggx: Ggx,
+    glass: GlassLobe,
+    /// The chances of sampling the diffuse, specular, glass and clearcoat
+    /// lobes.
+    probabilities: [f32; 4],
+}
+
+impl Shading {
+    /// Sums the lobes, each weighted by its share of the material and by
+    /// the cosine, for the local direction wi.
+    fn eval(&self, wi: Vector3) -> Vector3 {
+        let wo = self.wo;
+        let glass = self.glass.eval(wo, wi) * self.glass_weight;
+        if wi.z() < 0.0 {
+            return self.base_color * (self.base_weight * glass);
+        }
+        if wi.z() == 0.0 {
+            return Vector3::default();
+        }
+
+        let h = (wo + wi).normalized();
+        let cos_d = wi.dot(h);
+        let (fl, fv) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+
+        // Burley's diffuse with retro-reflection at rough grazing angles,
+        // blended with the Hanrahan-Krueger inspired subsurface term.
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let fss90 = self.roughness * cos_d * cos_d;
+        let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+        let ss = 1.25 * (fss * (1.0 / (wi.z() + wo.z()) - 0.5) + 0.5);
+        let diffuse = self.base_color
+            * (FRAC_1_PI * ((1.0 - self.subsurface) * fd + self.subsurface * ss))
+            + self.sheen * schlick_weight(cos_d);
+
+        let microfacet = self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z() * wi.z());
+        let specular = fresnel_schlick(self.specular_f0, cos_d) * microfacet;
+
+        let coat = self.coat_ggx.d(h) * self.coat_ggx.g2(wo, wi) / (4.0 * wo.z() * wi.z())
+            * self.clearcoat
+            * (CLEARCOAT_F0 + (1.0 - CLEARCOAT_F0) * schlick_weight(cos_d));
+
+        let base = (self.diffuse_weight * diffuse + (1.0 - self.glass_weight) * specular) * wi.z()
+            + Vector3::new(glass, glass, glass);
+        self.base_weight * base + Vector3::new(coat, coat, coat) * wi.z()
+    }
+
+    /// Sums the densities of the lobes for the local direction wi, each
+    /// weighted by the chance that `sample` picks it.
+    fn pdf(&self, wi: Vector3) -> f32 {
+        let wo = self.wo;
+        let [diffuse, specular, glass, coat] = self.probabilities;
+        let glass = glass * self.glass.pdf(wo, wi);
+        if wi.z() <= 0.0 {
+            return glass;
+        }
+        let h = (wo + wi).normalized();
+        diffuse * wi.z() * FRAC_1_PI
+            + specular * self.ggx.reflection_pdf(wo, h)
+            + glass
+            + coat * self.coat_ggx.reflection_pdf(wo, h)
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Option<Vector3> {
+        let [diffuse, specular, glass, _] = self.probabilities;
+        let choice = next_random(rng);
+        if choice < diffuse {
+            return Some((Vector3::new(0.0, 0.0, 1.0) + random_unit_vector(rng)).normalized());
+        }
+        if choice >= diffuse + specular && choice < diffuse + specular + glass {
+            return self.glass.sample(self.wo, rng);
+        }
+        let ggx = if choice < diffuse + specular {
+            &self.ggx
+        } else {
+            &self.coat_ggx
+        };
+        let h = ggx.sample_visible_normal(self.wo, next_random(rng), next_random(rng));
+        Some(reflect(-self.wo, h)).filter(|wi| wi.z() > 0.0)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let shading = self.shading(ray, hit)?;
+        let wi = shading.sample(rng)?;
+        let pdf = shading.pdf(wi);
+        // Without multiple scattering between microfacets, a reflection
+        // that points into the surface ends the path.
+        if pdf.is_nan() || pdf <= 0.0 {
+            return None;
+        }
+        let direction = shading.frame.world(wi);
+        let ray_out = Ray::new(hit.point(), direction, ray.time());
+        Some(ScatterRecord::new(ray_out, shading.eval(wi) / pdf, pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> Vector3 {
+        match self.shading(ray, hit) {
+            Some(shading) => shading.eval(shading.frame.local(direction)),
+            None => Vector3::default(),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: Vector3) -> f32 {
+        match self.shading(ray, hit) {
+            Some(shading) => shading.pdf(shading.frame.local(direction)),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::test_support::{check_sampling, eval_between};
+    use crate::math::seeded_rng;
+
+    #[test]
+    fn samples_match_eval_and_pdf() {
+        let mut rng = seeded_rng(7);
+        let outside = Ray::new(
+            Vector3::new(-1.0, 1.0, 0.3),
+            Vector3::new(1.0, -1.0, -0.3),
+            0.0,
+        );
+        let inside = Ray::new(
+            Vector3::new(-1.0, -1.0, 0.3),
+            Vector3::new(1.0, 1.0, -0.3),
+            0.0,
+        );
+        let materials = vec![
+            Principled::new(ConstantTexture::gray(0.6)),
+            Principled::new(ConstantTexture::gray(0.6))
+                .with_subsurface(ConstantTexture::gray(1.0))
+                .with_sheen(ConstantTexture::gray(1.0))
+                .with_roughness(ConstantTexture::gray(0.9)),
+            Principled::new(ConstantTexture::gray(0.8))
+                .with_metallic(ConstantTexture::gray(1.0))
+                .with_roughness(ConstantTexture::gray(0.3))
+                .with_clearcoat(ConstantTexture::gray(1.0))
+                .with_clearcoat_roughness(ConstantTexture::gray(0.2)),
+            Principled::new(ConstantTexture::gray(1.0))
+                .with_transmission(ConstantTexture::gray(1.0))
+                .with_roughness(ConstantTexture::gray(0.7)),
+        ];
+        for material in &materials {
+            for ray in &[outside, inside] {
+                check_sampling(material, ray, &mut rng, 1.02, 0.03);
+            }
+        }
+    }
+
+    #[test]
+    fn metallic_and_specular_blend_between_their_limits() {
+        // Seen and lit along the normal, the diffuse lobe reflects the base
+        // color over pi, and the specular lobe its reflectance at normal
+        // incidence times the microfacet term.
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let base_color = Vector3::new(0.8, 0.4, 0.1);
+        let eval = |metallic: f32, specular: f32| {
+            let material = Principled::new(ConstantTexture::new(base_color))
+                .with_metallic(ConstantTexture::gray(metallic))
+                .with_specular(ConstantTexture::gray(specular));
+            eval_between(&material, up, up)
+        };
+        let z = Vector3::new(0.0, 0.0, 1.0);
+        let ggx = Ggx::from_roughness(0.5);
+        let microfacet = ggx.d(z) * ggx.g2(z, z) / 4.0;
+        let close = |a: Vector3, b: Vector3| (a - b).length() < 1e-4 * b.length().max(1.0);
+
+        // A dielectric without specular reflection is purely diffuse, and
+        // full specular adds 8% reflectance in every channel.
+        assert!(close(eval(0.0, 0.0), base_color * FRAC_1_PI));
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        assert!(close(
+            eval(0.0, 1.0) - eval(0.0, 0.0),
+            0.08 * microfacet * white
+        ));
+        // A metal has no diffuse lobe, reflects its base color and ignores
+        // the specular setting.
+        assert!(close(eval(1.0, 0.0), base_color * microfacet));
+        assert!(close(eval(1.0, 1.0), base_color * microfacet));
+        // In between, the material blends the two linearly.
+        assert!(close(
+            eval(0.5, 0.5),
+            0.5 * (eval(0.0, 0.5) + eval(1.0, 0.5))
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+//! Checks shared by the tests of the materials.
+
+use std::f32::consts::PI;
+
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::{random_unit_vector, Rng};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Checks that a material scatters light arriving along the ray at a
+/// surface facing +y in agreement with its `eval` and `pdf`.
+///
+/// The mean weight of scattered paths is the directional albedo, which
+/// must not exceed `max_albedo` and must agree, within `tolerance`, with
+/// integrating the BSDF over uniformly chosen directions. Paths sampled
+/// below a microfacet are lost, so the density may integrate to a little
+/// less than one but never more.
+pub(super) fn check_sampling(
+    material: &dyn Material,
+    ray: &Ray,
+    rng: &mut Rng,
+    max_albedo: f64,
+    tolerance: f64,
+) {
+    let normal = Vector3::new(0.0, 1.0, 0.0);
+    let hit = HitRecord::new(1.0, Vector3::default(), normal, 0.0, 0.0, material);
+    let uniform = f64::from(4.0 * PI);
+    let n = 200_000;
+    let mut sampled = 0.0;
+    let mut integrated = 0.0;
+    let mut pdf_total = 0.0;
+    for _ in 0..n {
+        if let Some(scatter) = material.scatter(ray, &hit, rng) {
+            let direction = scatter.ray().direction();
+            let pdf = material.pdf(ray, &hit, direction);
+            assert!((pdf - scatter.pdf().unwrap()).abs() <= 1e-3 * pdf);
+            sampled += f64::from(scatter.attenuation().x());
+        }
+        let w = random_unit_vector(rng);
+        integrated += f64::from(material.eval(ray, &hit, w).x()) * uniform;
+        pdf_total += f64::from(material.pdf(ray, &hit, w)) * uniform;
+    }
+    let (sampled, integrated) = (sampled / n as f64, integrated / n as f64);
+    assert!(sampled <= max_albedo, "{:?}: {}", material, sampled);
+    assert!(
+        (sampled - integrated).abs() < tolerance,
+        "{:?}: {} {}",
+        material,
+        sampled,
+        integrated
+    );
+    assert!(pdf_total / n as f64 <= 1.02, "{:?}", material);
+}
+
+/// Returns the light, weighted by the cosine, that a material at a surface
+/// facing +y reflects from the unit direction `wi` towards the unit
+/// direction `wo`.
+pub(super) fn eval_between(material: &dyn Material, wo: Vector3, wi: Vector3) -> Vector3 {
+    let normal = Vector3::new(0.0, 1.0, 0.0);
+    let hit = HitRecord::new(1.0, Vector3::default(), normal, 0.0, 0.0, material);
+    material.eval(&Ray::new(wo, -wo, 0.0), &hit, wi)
+}
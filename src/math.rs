@@ -1,20 +1,60 @@
-use rand::random;
+use rand::{Rng as _, SeedableRng};
+use rand_pcg::Pcg32;
 use std::f32::consts::{FRAC_PI_2, PI};
 
 use crate::vector::Vector3;
 
+/// The random number generator used for all sampling. It is a fixed algorithm
+/// rather than the thread generator so that a render can be reproduced
+/// exactly from its seed.
+pub type Rng = Pcg32;
+
+/// Returns a generator seeded from the seed alone.
+pub fn seeded_rng(seed: u64) -> Rng {
+    Rng::seed_from_u64(seed)
+}
+
+/// Returns a generator for the samples of one pixel. Every pixel draws from
+/// its own sequence, so the image does not depend on which thread renders
+/// which pixel.
+pub fn pixel_rng(seed: u64, x: u32, y: u32) -> Rng {
+    let pixel = (u64::from(y) << 32) | u64::from(x);
+    Rng::new(mix(seed ^ mix(pixel)), mix(seed))
+}
+
+/// Scrambles the bits of a value using the SplitMix64 finalizer, so that
+/// nearby seeds give unrelated sequences.
+#[inline]
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns a number between 0 and 1 that is a hash of the bits of the
+/// values, for random choices that must depend only on their inputs.
+#[inline]
+pub fn hashed_random(values: &[f32]) -> f32 {
+    let hash = values
+        .iter()
+        .fold(0, |hash, value| mix(hash ^ u64::from(value.to_bits())));
+    // The top 24 bits fill the mantissa of an f32 in [0, 1) exactly.
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
 /// Returns a random number between 0 and 1.
 #[inline]
-pub fn next_random() -> f32 {
-    random::<f32>()
+pub fn next_random(rng: &mut Rng) -> f32 {
+    rng.gen::<f32>()
 }
 
 /// Returns a random point within a unit sphere.
 #[inline]
-pub fn random_in_unit_sphere() -> Vector3 {
-    let radius = random::<f32>();
-    let theta = 2.0 * PI * random::<f32>();
-    let phi = PI * random::<f32>() - FRAC_PI_2;
+pub fn random_in_unit_sphere(rng: &mut Rng) -> Vector3 {
+    let radius = next_random(rng);
+    let theta = 2.0 * PI * next_random(rng);
+    let phi = PI * next_random(rng) - FRAC_PI_2;
     Vector3::new(
         radius * theta.cos(),
         radius * theta.sin(),
@@ -22,11 +62,34 @@ pub fn random_in_unit_sphere() -> Vector3 {
     )
 }
 
+/// Returns a random direction, uniformly distributed over the unit sphere.
+#[inline]
+pub fn random_unit_vector(rng: &mut Rng) -> Vector3 {
+    let z = 1.0 - 2.0 * next_random(rng);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * next_random(rng);
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Returns two unit vectors perpendicular to each other and to the unit
+/// vector w, completing a right-handed orthonormal basis.
+#[inline]
+pub fn orthonormal_basis(w: Vector3) -> (Vector3, Vector3) {
+    let a = if w.x().abs() > 0.9 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let v = w.cross(a).normalized();
+    let u = v.cross(w);
+    (u, v)
+}
+
 /// Returns a random point within a unit disc on the XY plane.
 #[inline]
-pub fn random_in_unit_disc() -> Vector3 {
-    let radius = random::<f32>();
-    let theta = 2.0 * PI * random::<f32>();
+pub fn random_in_unit_disc(rng: &mut Rng) -> Vector3 {
+    let radius = next_random(rng);
+    let theta = 2.0 * PI * next_random(rng);
     Vector3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
 }
 
@@ -59,3 +122,10 @@ pub fn schlick(cosine: f32, ri: f32) -> f32 {
     let r0 = r0 * r0;
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+/// Returns the mean of the channels of a color, used to weigh the chance of
+/// sampling a lobe against the light it reflects.
+#[inline]
+pub fn average(color: Vector3) -> f32 {
+    (color.x() + color.y() + color.z()) / 3.0
+}
@@ -0,0 +1,465 @@
+//! Reads triangle meshes from Wavefront OBJ files.
+//!
+//! Vertex positions (`v`), texture coordinates (`vt`), normals (`vn`) and
+//! faces (`f`) are read, and `usemtl` starts a new material group. Faces with
+//! more than three vertices are split into triangles by ear clipping, so that
+//! concave polygons keep their shape. Vertex colors following a position, and
+//! other statements, such as `o`, `g`, `s` and `mtllib`, are ignored.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::material::Material;
+use crate::surface::{MeshFace, TriangleMesh};
+use crate::vector::Vector3;
+
+/// The geometry read from an OBJ file, before materials are assigned.
+#[derive(Debug, Default)]
+pub struct ObjModel {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<[f32; 2]>,
+    faces: Vec<MeshFace>,
+    groups: Vec<String>,
+}
+
+impl ObjModel {
+    pub fn load<T>(path: T) -> Result<ObjModel, ObjError>
+    where
+        T: AsRef<Path>,
+    {
+        let source = fs::read_to_string(path)
+            .map_err(|err| ObjError::new(0, format!("cannot read file: {}", err)))?;
+        ObjModel::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<ObjModel, ObjError> {
+        let mut model = ObjModel::default();
+        let mut group: Option<usize> = None;
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let args: Vec<&str> = words.collect();
+            let error = |message: String| ObjError::new(line_number, message);
+            match keyword {
+                "v" => {
+                    // Scanners and sculpting tools may follow the position
+                    // with an RGB color instead of a weight.
+                    let max = if args.len() == 6 { 6 } else { 4 };
+                    let v = numbers(&args, 3, max).map_err(|m| error(format!("vertex {}", m)))?;
+                    model.positions.push(Vector3::new(v[0], v[1], v[2]));
+                }
+                "vn" => {
+                    let n = numbers(&args, 3, 3).map_err(|m| error(format!("normal {}", m)))?;
+                    model.normals.push(Vector3::new(n[0], n[1], n[2]));
+                }
+                "vt" => {
+                    let t = numbers(&args, 1, 3)
+                        .map_err(|m| error(format!("texture coordinate {}", m)))?;
+                    model.uvs.push([t[0], t.get(1).cloned().unwrap_or(0.0)]);
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    if name.is_empty() {
+                        return Err(error("usemtl is missing a material name".to_string()));
+                    }
+                    group = Some(match model.groups.iter().position(|g| *g == name) {
+                        Some(index) => index,
+                        None => {
+                            model.groups.push(name);
+                            model.groups.len() - 1
+                        }
+                    });
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(error(format!(
+                            "face needs at least 3 vertices but has {}",
+                            args.len()
+                        )));
+                    }
+                    let vertices = args
+                        .iter()
+                        .map(|arg| model.face_vertex(arg))
+                        .collect::<Result<Vec<_>, String>>()
+                        .map_err(error)?;
+                    let material = match group {
+                        Some(index) => index,
+                        None => {
+                            // Faces before the first usemtl belong to an
+                            // unnamed default group.
+                            model.groups.push(String::new());
+                            group = Some(model.groups.len() - 1);
+                            model.groups.len() - 1
+                        }
+                    };
+                    model.triangulate(&vertices, material).map_err(error)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(model)
+    }
+
+    /// Returns the names of the material groups in the order they first
+    /// appear. Faces given before any `usemtl` belong to a group with an
+    /// empty name.
+    #[inline]
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    /// Builds a mesh from the model, choosing the material for each group by
+    /// calling `material` with the group name.
+    pub fn into_mesh<F>(self, mut material: F) -> TriangleMesh
+    where
+        F: FnMut(&str) -> Box<dyn Material>,
+    {
+        let materials = self.groups.iter().map(|name| material(name)).collect();
+        TriangleMesh::new(
+            self.positions,
+            self.normals,
+            self.uvs,
+            self.faces,
+            materials,
+        )
+    }
+
+    /// Resolves a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`
+    /// into zero-based indices.
+    fn face_vertex(&self, arg: &str) -> Result<(usize, Option<usize>, Option<usize>), String> {
+        let mut parts = arg.split('/');
+        let position = resolve(parts.next(), self.positions.len(), "vertex")?
+            .ok_or_else(|| format!("face vertex '{}' has no position", arg))?;
+        let uv = resolve(parts.next(), self.uvs.len(), "texture coordinate")?;
+        let normal = resolve(parts.next(), self.normals.len(), "normal")?;
+        if parts.next().is_some() {
+            return Err(format!("malformed face vertex '{}'", arg));
+        }
+        Ok((position, uv, normal))
+    }
+
+    /// Splits a polygon into triangles by clipping off one ear at a time, a
+    /// corner whose triangle lies inside the polygon. Convex polygons come
+    /// out as a fan of triangles around their first vertex.
+    fn triangulate(
+        &mut self,
+        vertices: &[(usize, Option<usize>, Option<usize>)],
+        material: usize,
+    ) -> Result<(), String> {
+        let has_uvs = vertices[0].1.is_some();
+        let has_normals = vertices[0].2.is_some();
+        if vertices
+            .iter()
+            .any(|v| v.1.is_some() != has_uvs || v.2.is_some() != has_normals)
+        {
+            return Err("face mixes vertices with and without attributes".to_string());
+        }
+        let points = self.project(vertices);
+        let mut remaining: Vec<usize> = (0..vertices.len()).collect();
+        while remaining.len() > 3 {
+            let n = remaining.len();
+            let ear = (1..=n)
+                .map(|i| i % n)
+                .find(|&i| is_ear(&points, &remaining, i))
+                .ok_or_else(|| "face is not a simple polygon".to_string())?;
+            let corners = [
+                vertices[remaining[(ear + n - 1) % n]],
+                vertices[remaining[ear]],
+                vertices[remaining[(ear + 1) % n]],
+            ];
+            self.push_triangle(corners, material);
+            remaining.remove(ear);
+        }
+        let corners = [
+            vertices[remaining[0]],
+            vertices[remaining[1]],
+            vertices[remaining[2]],
+        ];
+        self.push_triangle(corners, material);
+        Ok(())
+    }
+
+    /// Projects the corners of a polygon onto the axis plane it faces most,
+    /// ordered so that they run anticlockwise about its normal.
+    fn project(&self, vertices: &[(usize, Option<usize>, Option<usize>)]) -> Vec<[f32; 2]> {
+        // Newell's method gives a normal for polygons that are not quite
+        // flat, whose length is twice their area.
+        let mut normal = Vector3::default();
+        for (i, vertex) in vertices.iter().enumerate() {
+            let a = self.positions[vertex.0];
+            let b = self.positions[vertices[(i + 1) % vertices.len()].0];
+            normal = normal + a.cross(b);
+        }
+        let axis = (0..3)
+            .max_by(|&i, &j| normal[i].abs().total_cmp(&normal[j].abs()))
+            .unwrap();
+        let (x, y) = ((axis + 1) % 3, (axis + 2) % 3);
+        let sign = if normal[axis] < 0.0 { -1.0 } else { 1.0 };
+        vertices
+            .iter()
+            .map(|vertex| {
+                let p = self.positions[vertex.0];
+                [sign * p[x], p[y]]
+            })
+            .collect()
+    }
+
+    fn push_triangle(
+        &mut self,
+        corners: [(usize, Option<usize>, Option<usize>); 3],
+        material: usize,
+    ) {
+        let uvs = match (corners[0].1, corners[1].1, corners[2].1) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        };
+        let normals = match (corners[0].2, corners[1].2, corners[2].2) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        };
+        let positions = [corners[0].0, corners[1].0, corners[2].0];
+        self.faces
+            .push(MeshFace::new(positions, normals, uvs, material));
+    }
+}
+
+/// Returns twice the signed area of the triangle, positive when its corners
+/// run anticlockwise.
+#[inline]
+fn orientation(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Returns whether the corner at position `i` of the remaining polygon can
+/// be clipped: it is not reflex and no other corner lies inside its triangle.
+/// Corners on a straight edge count as ears, adding a triangle of no area.
+fn is_ear(points: &[[f32; 2]], remaining: &[usize], i: usize) -> bool {
+    let n = remaining.len();
+    let (prev, next) = ((i + n - 1) % n, (i + 1) % n);
+    let (a, b, c) = (
+        points[remaining[prev]],
+        points[remaining[i]],
+        points[remaining[next]],
+    );
+    if orientation(a, b, c) < 0.0 {
+        return false;
+    }
+    remaining.iter().enumerate().all(|(j, &index)| {
+        let p = points[index];
+        j == prev
+            || j == i
+            || j == next
+            || p == a
+            || p == b
+            || p == c
+            || orientation(a, b, p) <= 0.0
+            || orientation(b, c, p) <= 0.0
+            || orientation(c, a, p) <= 0.0
+    })
+}
+
+/// Parses between `min` and `max` numbers, describing the problem on failure.
+fn numbers(args: &[&str], min: usize, max: usize) -> Result<Vec<f32>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!(
+            "expects {} numbers but has {}",
+            expected,
+            args.len()
+        ));
+    }
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f32>()
+                .map_err(|_| format!("has invalid number '{}'", arg))
+        })
+        .collect()
+}
+
+/// Converts a one-based or negative relative OBJ index into a zero-based
+/// index, treating a missing or empty index as absent.
+fn resolve(index: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+    let index = match index {
+        Some(index) if !index.is_empty() => index,
+        _ => return Ok(None),
+    };
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", kind, index))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} is out of range, {} defined so far",
+            kind, value, count
+        ));
+    }
+    Ok(Some(resolved as usize))
+}
+
+/// An error found while reading an OBJ file, reported against the line it was
+/// found on. Errors not tied to any line use line zero.
+#[derive(Debug)]
+pub struct ObjError {
+    line: usize,
+    message: String,
+}
+
+impl ObjError {
+    pub fn new(line: usize, message: impl Into<String>) -> ObjError {
+        ObjError {
+            line,
+            message: message.into(),
+        }
+    }
+
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}: {}", self.line, self.message)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::surface::Surface;
+    use crate::texture::ConstantTexture;
+
+    const QUAD: &str = "\
+# A unit quad in the XY plane split across two materials.
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+usemtl blue
+f -4 -2 -1
+";
+
+    #[test]
+    fn reads_faces_and_groups() {
+        let model = ObjModel::parse(QUAD).unwrap();
+        assert_eq!(model.groups(), &["red".to_string(), "blue".to_string()]);
+        assert_eq!(model.faces.len(), 3);
+        assert_eq!(
+            model.faces[1],
+            MeshFace::new([0, 2, 3], Some([0, 0, 0]), Some([0, 2, 3]), 0)
+        );
+        assert_eq!(model.faces[2], MeshFace::new([0, 2, 3], None, None, 1));
+    }
+
+    #[test]
+    fn mesh_reports_interpolated_coordinates() {
+        let mesh = ObjModel::parse(QUAD)
+            .unwrap()
+            .into_mesh(|_| Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))));
+        let ray = Ray::new(
+            Vector3::new(0.25, 0.75, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let hit = mesh.hit(&ray, 0.001, 10.0).unwrap();
+        assert!((hit.t() - 1.0).abs() < 1e-6);
+        assert!((hit.u() - 0.25).abs() < 1e-6);
+        assert!((hit.v() - 0.75).abs() < 1e-6);
+        assert_eq!(hit.normal(), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn clips_ears_from_concave_faces() {
+        // An L shape whose fan around the first vertex would cover the
+        // missing corner, with RGB colors after the positions.
+        let source = "\
+v 0 0 0 1 0 0
+v 2 0 0 1 0 0
+v 2 1 0 1 0 0
+v 1 1 0 1 0 0
+v 1 2 0 1 0 0
+v 0 2 0 1 0 0
+f 5 6 1 2 3 4
+";
+        let model = ObjModel::parse(source).unwrap();
+        assert_eq!(model.faces.len(), 4);
+        let mut area = 0.0;
+        for face in &model.faces {
+            let p = face.positions();
+            let (a, b, c) = (
+                model.positions[p[0]],
+                model.positions[p[1]],
+                model.positions[p[2]],
+            );
+            let normal = (b - a).cross(c - a);
+            assert!(normal.z() >= 0.0, "{:?}", face);
+            area += 0.5 * normal.length();
+        }
+        assert!((area - 3.0).abs() < 1e-6);
+
+        let mesh = model
+            .into_mesh(|_| Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))));
+        let down = |x, y| Ray::new(Vector3::new(x, y, 1.0), Vector3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(mesh.hit(&down(1.2, 1.2), 0.001, 10.0).is_none());
+        assert!(mesh.hit(&down(0.5, 1.5), 0.001, 10.0).is_some());
+        assert!(mesh.hit(&down(1.5, 0.5), 0.001, 10.0).is_some());
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let line_of = |source| ObjModel::parse(source).unwrap_err().line();
+        assert_eq!(line_of("v 0 0 0\nv 1 x 0\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0 1 1\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 4\n"), 5);
+        assert_eq!(
+            line_of("v 0 0 0\nv 1 0 0\nv 1 1 0\nvn 0 0 1\nf 1//1 2 3\n"),
+            5
+        );
+        let err = ObjModel::parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 0\n").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "vertex index 0 is out of range, 3 defined so far"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+
+use crate::render::Heuristic;
+use crate::tonemap::{Overflow, ToneMap, ToneOperator};
+
+/// Description of the command line printed by `--help`.
+pub const USAGE: &str = "\
+Usage: raytracer [OPTIONS] [SCENE]
+
+Renders SCENE, or the built-in demo scene if no scene file is given.
+
+Options:
+  -i, --scene <PATH>        scene file to render
+  -o, --output <PATH>       output image, .png, .hdr or .exr [default: raytracer.png]
+  -W, --width <PIXELS>      image width [default: 640]
+  -H, --height <PIXELS>     image height [default: 480]
+  -s, --samples <COUNT>     samples per pixel [default: 128]
+  -t, --threads <COUNT>     worker threads [default: number of cores]
+  -d, --max-depth <COUNT>   maximum number of bounces per path [default: 50]
+      --min-depth <COUNT>   bounces before Russian roulette may end a path
+                            [default: 3]
+      --seed <NUMBER>       seed for all random sampling [default: 0]
+      --mis <HEURISTIC>     balance or power weighting of light and BSDF
+                            samples [default: power]
+      --spectral            trace single wavelengths instead of RGB, for
+                            dispersion
+      --exposure <STOPS>    exposure adjustment for .png output [default: 0]
+      --tonemap <CURVE>     clamp, reinhard or aces [default: clamp]
+      --overflow <POLICY>   clip or preserve-hue for colors above white
+                            [default: clip]
+  -h, --help                print this help and exit";
+
+/// Settings for a render, read from the command line.
+#[derive(Debug)]
+pub struct Options {
+    scene: Option<PathBuf>,
+    output: PathBuf,
+    width: u32,
+    height: u32,
+    samples: u32,
+    threads: u32,
+    max_depth: u32,
+    min_depth: u32,
+    seed: u64,
+    heuristic: Heuristic,
+    spectral: bool,
+    exposure: f32,
+    tone_operator: ToneOperator,
+    overflow: Overflow,
+    help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get() as u32);
+        Options {
+            scene: None,
+            output: PathBuf::from("raytracer.png"),
+            width: 640,
+            height: 480,
+            samples: 128,
+            threads,
+            max_depth: 50,
+            min_depth: 3,
+            seed: 0,
+            heuristic: Heuristic::Power,
+            spectral: false,
+            exposure: 0.0,
+            tone_operator: ToneOperator::Clamp,
+            overflow: Overflow::Clip,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the command line arguments, not including the program name.
+    pub fn parse<I>(args: I) -> Result<Options, OptionsError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`.
+            let (flag, inline) = match arg.find('=') {
+                Some(index) if arg.starts_with("--") => {
+                    (arg[..index].to_string(), Some(arg[index + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| OptionsError(format!("missing value for {}", flag)))
+            };
+            match flag.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-i" | "--scene" => options.scene = Some(PathBuf::from(value()?)),
+                "-o" | "--output" => options.output = PathBuf::from(value()?),
+                "-W" | "--width" => options.width = positive(&flag, &value()?)?,
+                "-H" | "--height" => options.height = positive(&flag, &value()?)?,
+                "-s" | "--samples" => options.samples = positive(&flag, &value()?)?,
+                "-t" | "--threads" => options.threads = positive(&flag, &value()?)?,
+                "-d" | "--max-depth" => options.max_depth = positive(&flag, &value()?)?,
+                "--min-depth" => options.min_depth = parsed(&flag, &value()?)?,
+                "--seed" => options.seed = parsed(&flag, &value()?)?,
+                "--mis" => options.heuristic = parsed(&flag, &value()?)?,
+                "--spectral" => options.spectral = true,
+                "--exposure" => options.exposure = finite(&flag, &value()?)?,
+                "--tonemap" => options.tone_operator = parsed(&flag, &value()?)?,
+                "--overflow" => options.overflow = parsed(&flag, &value()?)?,
+                _ if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(OptionsError(format!("unknown option {}", flag)));
+                }
+                _ => {
+                    if let Some(scene) = &options.scene {
+                        return Err(OptionsError(format!(
+                            "unexpected argument {} after scene {}",
+                            arg,
+                            scene.display()
+                        )));
+                    }
+                    options.scene = Some(PathBuf::from(arg));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    #[inline]
+    pub fn scene(&self) -> Option<&Path> {
+        self.scene.as_deref()
+    }
+
+    #[inline]
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    #[inline]
+    pub fn threads(&self) -> u32 {
+        self.threads
+    }
+
+    #[inline]
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Returns the number of bounces after which paths are subject to
+    /// Russian roulette.
+    #[inline]
+    pub fn min_depth(&self) -> u32 {
+        self.min_depth
+    }
+
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    #[inline]
+    pub fn heuristic(&self) -> Heuristic {
+        self.heuristic
+    }
+
+    /// Returns whether paths are traced at single wavelengths, so that
+    /// materials can depend on wavelength.
+    #[inline]
+    pub fn spectral(&self) -> bool {
+        self.spectral
+    }
+
+    /// Returns the transform used to convert the linear render to a display
+    /// image.
+    #[inline]
+    pub fn tone_map(&self) -> ToneMap {
+        ToneMap::new(self.exposure, self.tone_operator, self.overflow)
+    }
+
+    #[inline]
+    pub fn help(&self) -> bool {
+        self.help
+    }
+}
+
+/// An invalid command line.
+#[derive(Debug)]
+pub struct OptionsError(String);
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+/// Parses a value of the flag into any setting: a number, or one of the
+/// names of an enumerated setting.
+fn parsed<T: FromStr>(flag: &str, value: &str) -> Result<T, OptionsError> {
+    value
+        .parse()
+        .map_err(|_| OptionsError(format!("invalid value '{}' for {}", value, flag)))
+}
+
+fn positive(flag: &str, value: &str) -> Result<u32, OptionsError> {
+    match parsed(flag, value)? {
+        0 => Err(OptionsError(format!("{} must be at least 1", flag))),
+        n => Ok(n),
+    }
+}
+
+fn finite(flag: &str, value: &str) -> Result<f32, OptionsError> {
+    let n: f32 = parsed(flag, value)?;
+    if n.is_finite() {
+        Ok(n)
+    } else {
+        Err(OptionsError(format!("{} must be a finite number", flag)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, OptionsError> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_flags() {
+        let options =
+            parse("-W 320 --height=200 -s 16 --threads 3 -d 8 -o out.png --seed 42 scene.txt")
+                .unwrap();
+        assert_eq!((options.width(), options.height()), (320, 200));
+        assert_eq!((options.samples(), options.threads()), (16, 3));
+        assert_eq!(options.max_depth(), 8);
+        assert_eq!(options.min_depth(), 3);
+        assert_eq!(options.seed(), 42);
+        assert_eq!(options.heuristic(), Heuristic::Power);
+        assert!(!options.spectral());
+        assert_eq!(options.tone_map(), ToneMap::default());
+        assert_eq!(options.output(), Path::new("out.png"));
+        assert_eq!(options.scene(), Some(Path::new("scene.txt")));
+        assert!(!options.help());
+
+        let options = parse("--mis balance --min-depth 0 --spectral").unwrap();
+        assert_eq!(options.heuristic(), Heuristic::Balance);
+        assert_eq!(options.min_depth(), 0);
+        assert!(options.spectral());
+
+        let options = parse("--exposure -1.5 --tonemap aces --overflow=preserve-hue").unwrap();
+        assert_eq!(
+            options.tone_map(),
+            ToneMap::new(-1.5, ToneOperator::AcesFilmic, Overflow::PreserveHue)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let message = |args| parse(args).unwrap_err().to_string();
+        assert_eq!(message("--samples 0"), "--samples must be at least 1");
+        assert_eq!(message("-W wide"), "invalid value 'wide' for -W");
+        assert_eq!(message("--output"), "missing value for --output");
+        assert_eq!(
+            message("--tonemap filmic"),
+            "invalid value 'filmic' for --tonemap"
+        );
+        assert_eq!(
+            message("--exposure inf"),
+            "--exposure must be a finite number"
+        );
+        assert_eq!(message("--fast"), "unknown option --fast");
+        assert_eq!(message("a b"), "unexpected argument b after scene a");
+    }
+}
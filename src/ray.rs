@@ -1,18 +1,29 @@
 use crate::vector::Vector3;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Ray {
     origin: Vector3,
     direction: Vector3,
     time: f32,
+    wavelengths: Option<Vector3>,
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3, time: f32) -> Ray {
         Ray {
-            origin: origin,
-            direction: direction,
-            time: time,
+            origin,
+            direction,
+            time,
+            wavelengths: None,
+        }
+    }
+
+    /// Returns the ray carrying light of three single wavelengths in
+    /// nanometres, one for each channel, as traced by spectral renders.
+    pub fn with_wavelengths(self, wavelengths: Vector3) -> Ray {
+        Ray {
+            wavelengths: Some(wavelengths),
+            ..self
         }
     }
 
@@ -31,6 +42,20 @@ impl Ray {
         self.time
     }
 
+    /// Returns the wavelengths the ray carries in spectral renders, or None
+    /// when it carries RGB light.
+    #[inline]
+    pub fn wavelengths(&self) -> Option<Vector3> {
+        self.wavelengths
+    }
+
+    /// Returns the first of the wavelengths the ray carries, which decides
+    /// the path of light through materials that split up the wavelengths.
+    #[inline]
+    pub fn wavelength(&self) -> Option<f32> {
+        self.wavelengths.map(|wavelengths| wavelengths.x())
+    }
+
     #[inline]
     pub fn point_at_parameter(&self, t: f32) -> Vector3 {
         self.origin + t * self.direction
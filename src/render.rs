@@ -0,0 +1,591 @@
+use std::f32;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::hit_record::HitRecord;
+use crate::image::Image;
+use crate::math::{next_random, pixel_rng, Rng};
+use crate::options::Options;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::spectrum::{rgb_to_spectrum, sample_wavelengths, spectrum_to_rgb, wavelength_pdf};
+use crate::surface::Surface;
+use crate::vector::Vector3;
+
+/// Width and height in pixels of the tiles handed out to worker threads.
+const TILE_SIZE: u32 = 16;
+
+/// How the light and BSDF sampling strategies are weighted against each
+/// other when both can produce the same path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Heuristic {
+    /// Weights each strategy by its share of the summed densities.
+    Balance,
+    /// Weights each strategy by its share of the summed squared densities,
+    /// which favours the better strategy more strongly.
+    Power,
+}
+
+impl Heuristic {
+    /// Returns the weight of a sample drawn with density `pdf` by one
+    /// strategy, when the other strategy has density `other_pdf`.
+    #[inline]
+    pub fn weight(self, pdf: f32, other_pdf: f32) -> f32 {
+        let (a, b) = match self {
+            Heuristic::Balance => (pdf, other_pdf),
+            Heuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if a + b > 0.0 {
+            a / (a + b)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl FromStr for Heuristic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Heuristic, String> {
+        match s {
+            "balance" => Ok(Heuristic::Balance),
+            "power" => Ok(Heuristic::Power),
+            _ => Err(format!("unknown heuristic '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Heuristic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Heuristic::Balance => "balance",
+            Heuristic::Power => "power",
+        })
+    }
+}
+
+/// A rectangular block of pixels rendered as one unit of work.
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+/// Renders the scene into a new image.
+///
+/// The image is divided into tiles that worker threads take from a shared
+/// queue, so that threads finishing cheap tiles move straight on to the next
+/// one. Each worker renders into its own buffers, which are copied into the
+/// image once every tile is done, so no locks are taken while rendering.
+pub fn render(scene: &Scene, options: &Options) -> Image {
+    let nx = options.width();
+    let ny = options.height();
+    let tiles = tiles(nx, ny);
+    let next_tile = AtomicUsize::new(0);
+
+    let finished: Vec<(Tile, Vec<Vector3>)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..options.threads())
+            .map(|_| {
+                s.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let tile = match tiles.get(index) {
+                            Some(&tile) => tile,
+                            None => break,
+                        };
+                        finished.push((tile, render_tile(scene, options, tile)));
+                    }
+                    finished
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    let mut image = Image::new(nx, ny);
+    for (tile, pixels) in finished {
+        let mut pixels = pixels.into_iter();
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                // Flip the y axis to match the expected coordinate system.
+                image.set_pixel(x, ny - y - 1, pixels.next().unwrap());
+            }
+        }
+    }
+    image
+}
+
+/// Splits an image into tiles, in rows from the bottom of the image up.
+fn tiles(nx: u32, ny: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y0 in (0..ny).step_by(TILE_SIZE as usize) {
+        for x0 in (0..nx).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + TILE_SIZE).min(nx),
+                y1: (y0 + TILE_SIZE).min(ny),
+            });
+        }
+    }
+    tiles
+}
+
+fn render_tile(scene: &Scene, options: &Options, tile: Tile) -> Vec<Vector3> {
+    let mut pixels = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+    for y in tile.y0..tile.y1 {
+        for x in tile.x0..tile.x1 {
+            pixels.push(render_pixel(scene, options, x, y));
+        }
+    }
+    pixels
+}
+
+/// Computes the color of one pixel, counting y from the bottom of the image.
+/// Every pixel draws its samples from its own generator, so the result does
+/// not depend on the order in which pixels are rendered.
+fn render_pixel(scene: &Scene, options: &Options, x: u32, y: u32) -> Vector3 {
+    let nx = options.width();
+    let ny = options.height();
+    let nsamples = options.samples();
+    let mut rng = pixel_rng(options.seed(), x, y);
+    let mut c = Vector3::new(0.0, 0.0, 0.0);
+    for _ in 0..nsamples {
+        let u = (x as f32 + next_random(&mut rng)) / (nx as f32);
+        let v = (y as f32 + next_random(&mut rng)) / (ny as f32);
+        let ray = scene.camera().cast_ray(u, v, &mut rng);
+        c = c + if options.spectral() {
+            color_spectral(scene, options, &ray, &mut rng)
+        } else {
+            color(scene, options, &ray, &mut rng)
+        };
+    }
+    // Average the samples to determine pixel color.
+    c / nsamples as f32
+}
+
+/// Estimates the light arriving along the ray at three wavelengths chosen at
+/// random, returning the color they add to the pixel. Averaged over many
+/// paths, the wavelengths make up the full spectrum of the light.
+fn color_spectral(scene: &Scene, options: &Options, ray: &Ray, rng: &mut Rng) -> Vector3 {
+    let wavelengths = sample_wavelengths(rng);
+    let radiance = color(scene, options, &ray.with_wavelengths(wavelengths), rng);
+    let mut c = Vector3::default();
+    for i in 0..3 {
+        let wavelength = wavelengths[i];
+        c = c + spectrum_to_rgb(radiance[i], wavelength) / wavelength_pdf(wavelength);
+    }
+    c / 3.0
+}
+
+/// Returns the color as it is carried along a ray. Rays of single
+/// wavelengths carry the value of the spectrum of the color at each of them.
+#[inline]
+fn at_wavelengths(color: Vector3, wavelengths: Option<Vector3>) -> Vector3 {
+    match wavelengths {
+        Some(wavelengths) => Vector3::new(
+            rgb_to_spectrum(color, wavelengths.x()),
+            rgb_to_spectrum(color, wavelengths.y()),
+            rgb_to_spectrum(color, wavelengths.z()),
+        ),
+        None => color,
+    }
+}
+
+/// Keeps only the first of the wavelengths light is carried at, after the
+/// first material that sends each wavelength its own way, weighted up to
+/// stand for all three.
+#[inline]
+fn first_wavelength(color: Vector3) -> Vector3 {
+    Vector3::new(3.0 * color.x(), 0.0, 0.0)
+}
+
+/// Estimates the light arriving along the ray. Paths are extended by sampling
+/// the BSDF, and at every non-specular hit a light is also sampled directly.
+/// Both strategies can find the same emitter, so their contributions are
+/// combined with multiple importance sampling.
+///
+/// The path is followed in a loop that keeps the product of the attenuations
+/// so far. Once the path is longer than the minimum depth, Russian roulette
+/// ends it with a probability that grows as that product falls, and the
+/// paths that survive are weighted up to keep the estimate unbiased.
+///
+/// A ray of single wavelengths passes them on to the whole path, and the
+/// colors met along the path are taken at those wavelengths. A dispersive
+/// material sends each wavelength its own way, so from the first one on the
+/// path carries only the first wavelength.
+fn color(scene: &Scene, options: &Options, ray: &Ray, rng: &mut Rng) -> Vector3 {
+    let wavelengths = ray.wavelengths();
+    let mut radiance = Vector3::new(0.0, 0.0, 0.0);
+    let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+    let mut ray = *ray;
+    let mut bsdf_pdf: Option<f32> = None;
+    let mut dispersed = false;
+    for depth in 0..options.max_depth() {
+        let hit = match scene.world().hit(&ray, 0.01, f32::MAX) {
+            Some(hit) => hit,
+            None => {
+                let background = scene.background().value(ray.direction());
+                let mut background = at_wavelengths(background, wavelengths);
+                if let Some(bsdf_pdf) = bsdf_pdf {
+                    let light_pdf = light_pdf(scene, &ray);
+                    background = background * options.heuristic().weight(bsdf_pdf, light_pdf);
+                }
+                return radiance + throughput * background;
+            }
+        };
+        let material = hit.material();
+        let emitted = material.emitted(hit.u(), hit.v(), hit.point());
+        let mut emitted = at_wavelengths(emitted, wavelengths);
+        if let Some(bsdf_pdf) = bsdf_pdf {
+            // The ray was chosen by sampling the BSDF at the previous hit,
+            // where the emitter may also have been sampled directly.
+            let light_pdf = light_pdf(scene, &ray);
+            emitted = emitted * options.heuristic().weight(bsdf_pdf, light_pdf);
+        }
+        radiance = radiance + throughput * emitted;
+
+        let scatter = match material.scatter(&ray, &hit, rng) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        // A direction on opposite sides of the shading and geometric
+        // normals would let light leak through the surface, so such paths
+        // are ended as if absorbed.
+        if !hit.same_side(scatter.ray().direction()) {
+            break;
+        }
+        let mut direct = Vector3::default();
+        if !scatter.is_specular() {
+            direct = sample_light(scene, options, &ray, &hit, rng);
+        }
+        let mut attenuation = at_wavelengths(scatter.attenuation(), wavelengths);
+        if wavelengths.is_some() && material.is_dispersive() && !dispersed {
+            direct = first_wavelength(direct);
+            attenuation = first_wavelength(attenuation);
+            dispersed = true;
+        }
+        radiance = radiance + throughput * direct;
+        throughput = throughput * attenuation;
+
+        if depth + 1 >= options.min_depth() {
+            let survival = throughput
+                .x()
+                .max(throughput.y())
+                .max(throughput.z())
+                .min(0.95);
+            if next_random(rng) >= survival {
+                break;
+            }
+            throughput = throughput / survival;
+        }
+        bsdf_pdf = scatter.pdf();
+        ray = match wavelengths {
+            Some(wavelengths) => scatter.ray().with_wavelengths(wavelengths),
+            None => *scatter.ray(),
+        };
+    }
+    radiance
+}
+
+/// Estimates the light reaching a hit directly from a randomly chosen light,
+/// weighted against finding the same light by sampling the BSDF. A background
+/// that is not black counts as one more light.
+fn sample_light(
+    scene: &Scene,
+    options: &Options,
+    ray: &Ray,
+    hit: &HitRecord,
+    rng: &mut Rng,
+) -> Vector3 {
+    let black = Vector3::new(0.0, 0.0, 0.0);
+    let lights = scene.lights();
+    let background = scene.background();
+    let count = lights.len() + if background.is_black() { 0 } else { 1 };
+    if count == 0 {
+        return black;
+    }
+    let index = ((next_random(rng) * count as f32) as usize).min(count - 1);
+    let direction = match lights.get(index) {
+        Some(light) => light.random(hit.point(), ray.time(), rng).normalized(),
+        None => background.random(rng),
+    };
+    if !hit.same_side(direction) {
+        return black;
+    }
+    let shadow_ray = Ray::new(hit.point(), direction, ray.time());
+    let light_pdf = light_pdf(scene, &shadow_ray);
+    if light_pdf.is_nan() || light_pdf <= 0.0 {
+        return black;
+    }
+    let bsdf = at_wavelengths(hit.material().eval(ray, hit, direction), ray.wavelengths());
+    if bsdf == black {
+        return black;
+    }
+    // Whatever the shadow ray hits first is what the light sample sees, so
+    // occluded lights contribute only the emission of the occluder.
+    let radiance = match scene.world().hit(&shadow_ray, 0.01, f32::MAX) {
+        Some(light_hit) => {
+            light_hit
+                .material()
+                .emitted(light_hit.u(), light_hit.v(), light_hit.point())
+        }
+        None => background.value(direction),
+    };
+    let radiance = at_wavelengths(radiance, ray.wavelengths());
+    let bsdf_pdf = hit.material().pdf(ray, hit, direction);
+    let weight = options.heuristic().weight(light_pdf, bsdf_pdf);
+    bsdf * radiance * (weight / light_pdf)
+}
+
+/// Returns the solid angle density with which `sample_light` chooses the
+/// direction of the ray.
+fn light_pdf(scene: &Scene, ray: &Ray) -> f32 {
+    let lights = scene.lights();
+    let background = scene.background();
+    if background.is_black() {
+        return lights.pdf_value(ray);
+    }
+    let n = lights.len() as f32;
+    (n * lights.pdf_value(ray) + background.pdf_value(ray.direction())) / (n + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{build_camera, build_world};
+
+    fn options(args: &str) -> Options {
+        Options::parse(args.split_whitespace().map(String::from)).unwrap()
+    }
+
+    fn demo_scene(options: &Options) -> Scene {
+        let world = build_world(options.seed());
+        Scene::new(world, build_camera(options.width(), options.height()))
+    }
+
+    /// The previous integrator, which recurses to the maximum depth and
+    /// multiplies the attenuations on the way back up. Kept as a reference
+    /// for the iterative one.
+    fn color_recursive(scene: &Scene, options: &Options, ray: &Ray, rng: &mut Rng) -> Vector3 {
+        fn color_rec(
+            scene: &Scene,
+            options: &Options,
+            ray: &Ray,
+            depth: u32,
+            bsdf_pdf: Option<f32>,
+            rng: &mut Rng,
+        ) -> Vector3 {
+            if depth == 0 {
+                return Vector3::new(0.0, 0.0, 0.0);
+            }
+            // The ray was chosen by sampling the BSDF at the previous hit,
+            // where the light it finds may also have been sampled directly.
+            let weight = match bsdf_pdf {
+                Some(bsdf_pdf) => options.heuristic().weight(bsdf_pdf, light_pdf(scene, ray)),
+                None => 1.0,
+            };
+            let hit = match scene.world().hit(ray, 0.01, f32::MAX) {
+                Some(hit) => hit,
+                None => return scene.background().value(ray.direction()) * weight,
+            };
+            let material = hit.material();
+            let emitted = material.emitted(hit.u(), hit.v(), hit.point()) * weight;
+            let scatter = match material.scatter(ray, &hit, rng) {
+                Some(scatter) if hit.same_side(scatter.ray().direction()) => scatter,
+                _ => return emitted,
+            };
+            let direct = if scatter.is_specular() {
+                Vector3::new(0.0, 0.0, 0.0)
+            } else {
+                sample_light(scene, options, ray, &hit, rng)
+            };
+            let indirect = color_rec(scene, options, scatter.ray(), depth - 1, scatter.pdf(), rng);
+            emitted + direct + scatter.attenuation() * indirect
+        }
+        color_rec(scene, options, ray, options.max_depth(), None, rng)
+    }
+
+    /// The previous renderer, which interleaves rows across threads and locks
+    /// the shared image for every pixel. Kept as a reference for output and
+    /// for timing.
+    fn render_locked(scene: &Scene, options: &Options) -> Image {
+        let nx = options.width();
+        let ny = options.height();
+        let nthreads = options.threads();
+        let image = Arc::new(Mutex::new(Image::new(nx, ny)));
+        thread::scope(|s| {
+            for threadid in 0..nthreads {
+                let image = Arc::clone(&image);
+                s.spawn(move || {
+                    for y in (threadid..ny).step_by(nthreads as usize) {
+                        for x in 0..nx {
+                            let c = render_pixel(scene, options, x, y);
+                            let mut image = image.lock().unwrap();
+                            image.set_pixel(x, ny - y - 1, c);
+                        }
+                    }
+                });
+            }
+        });
+        Arc::try_unwrap(image).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn render_is_independent_of_thread_count() {
+        let single = options("-W 40 -H 30 -s 4 -t 1 --seed 5");
+        let scene = demo_scene(&single);
+        let expected = render(&scene, &single);
+        for threads in &[2, 3, 7] {
+            let args = format!("-W 40 -H 30 -s 4 -t {} --seed 5", threads);
+            assert_eq!(render(&scene, &options(&args)), expected);
+        }
+        assert_eq!(render_locked(&scene, &single), expected);
+    }
+
+    fn mean(image: &Image) -> Vector3 {
+        let sum = image
+            .pixels()
+            .iter()
+            .fold(Vector3::default(), |sum, &pixel| sum + pixel);
+        sum / image.pixels().len() as f32
+    }
+
+    /// Returns the average color over the image of the scene, estimated with
+    /// the given integrator.
+    fn mean_color(
+        scene: &Scene,
+        options: &Options,
+        integrator: fn(&Scene, &Options, &Ray, &mut Rng) -> Vector3,
+    ) -> Vector3 {
+        let (nx, ny) = (options.width(), options.height());
+        let mut sum = Vector3::default();
+        for y in 0..ny {
+            for x in 0..nx {
+                let mut rng = pixel_rng(options.seed(), x, y);
+                for _ in 0..options.samples() {
+                    let u = (x as f32 + next_random(&mut rng)) / nx as f32;
+                    let v = (y as f32 + next_random(&mut rng)) / ny as f32;
+                    let ray = scene.camera().cast_ray(u, v, &mut rng);
+                    sum = sum + integrator(scene, options, &ray, &mut rng);
+                }
+            }
+        }
+        sum / (nx * ny * options.samples()) as f32
+    }
+
+    #[test]
+    fn russian_roulette_matches_recursive_integrator() {
+        let options = options("-W 32 -H 24 -s 32 --min-depth 1 --seed 3");
+        let scene = demo_scene(&options);
+        let expected = mean_color(&scene, &options, color_recursive);
+        let estimate = mean_color(&scene, &options, color);
+        for axis in 0..3 {
+            let error = (estimate[axis] - expected[axis]).abs() / expected[axis];
+            assert!(error < 0.02, "{:?} {:?}", estimate, expected);
+        }
+    }
+
+    #[test]
+    fn light_sampling_converges_to_path_tracing() {
+        let lit = Scene::load("scenes/lamp.scene", 4.0 / 3.0).unwrap();
+        let unlit = Scene::load("scenes/lamp.scene", 4.0 / 3.0)
+            .unwrap()
+            .with_lights(Vec::new());
+        let reference = mean(&render(&unlit, &options("-W 24 -H 18 -s 256 -d 8")));
+        for heuristic in &["balance", "power"] {
+            let args = format!("-W 24 -H 18 -s 64 -d 8 --mis {}", heuristic);
+            let estimate = mean(&render(&lit, &options(&args)));
+            for axis in 0..3 {
+                let error = (estimate[axis] - reference[axis]).abs() / reference[axis];
+                assert!(
+                    error < 0.03,
+                    "{}: {:?} {:?}",
+                    heuristic,
+                    estimate,
+                    reference
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn spectral_render_matches_rgb() {
+        // Colors lose a little saturation on the way through a spectrum, but
+        // the light of a mostly gray scene must come out the same.
+        let scene = Scene::load("scenes/lamp.scene", 4.0 / 3.0).unwrap();
+        let reference = mean(&render(&scene, &options("-W 32 -H 24 -s 256 -d 8")));
+        let estimate = mean(&render(
+            &scene,
+            &options("-W 32 -H 24 -s 256 -d 8 --spectral"),
+        ));
+        for axis in 0..3 {
+            let error = (estimate[axis] - reference[axis]).abs() / reference[axis];
+            assert!(error < 0.03, "{:?} {:?}", estimate, reference);
+        }
+    }
+
+    #[test]
+    fn dispersion_keeps_the_light_of_white_backgrounds() {
+        // Lossless glass between the camera and an even white background
+        // passes all of its light on, however many times the path meets
+        // a dispersive surface.
+        let scene = Scene::parse(
+            "(camera (look-from 0 0 0) (look-at 0 0 -1) (vfov 30))
+             (background (constant 1 1 1))
+             (sphere (center 0 0 -3) (radius 1.5)
+                 (material (dielectric (cauchy 1.5 0.02))))",
+            1.0,
+        )
+        .unwrap();
+        let estimate = mean(&render(
+            &scene,
+            &options("-W 16 -H 16 -s 64 -d 16 --spectral"),
+        ));
+        for axis in 0..3 {
+            assert!((estimate[axis] - 1.0).abs() < 0.05, "{:?}", estimate);
+        }
+    }
+
+    /// Compares the time taken by the tiled and the locked renderers as the
+    /// number of threads grows. Run it with
+    /// `cargo test --release -- --ignored --nocapture benchmark`.
+    #[test]
+    #[ignore]
+    fn benchmark_scaling() {
+        let base = options("-W 320 -H 240 -s 16");
+        let scene = demo_scene(&base);
+        let max_threads = base.threads().max(8);
+        println!("threads    locked     tiled   speedup");
+        let mut threads = 1;
+        while threads <= max_threads {
+            let options = options(&format!("-W 320 -H 240 -s 16 -t {}", threads));
+            let start = Instant::now();
+            let locked = render_locked(&scene, &options);
+            let locked_time = start.elapsed().as_secs_f64();
+            let start = Instant::now();
+            let tiled = render(&scene, &options);
+            let tiled_time = start.elapsed().as_secs_f64();
+            assert!(locked == tiled);
+            println!(
+                "{:>7} {:>8.3}s {:>8.3}s {:>8.2}x",
+                threads,
+                locked_time,
+                tiled_time,
+                locked_time / tiled_time
+            );
+            threads *= 2;
+        }
+    }
+}
@@ -1,17 +1,34 @@
 use crate::ray::Ray;
 use crate::vector::Vector3;
 
+/// A direction chosen by a material for light to continue along, together
+/// with the factor the light arriving along it is multiplied by.
 #[derive(Debug)]
 pub struct ScatterRecord {
     ray: Ray,
     attenuation: Vector3,
+    pdf: Option<f32>,
 }
 
 impl ScatterRecord {
-    pub fn new(ray: Ray, attenuation: Vector3) -> ScatterRecord {
+    /// Creates a record for a direction drawn from a continuous distribution
+    /// with the given solid angle density. The attenuation is the BSDF times
+    /// the cosine term divided by that density.
+    pub fn new(ray: Ray, attenuation: Vector3, pdf: f32) -> ScatterRecord {
         ScatterRecord {
-            ray: ray,
-            attenuation: attenuation,
+            ray,
+            attenuation,
+            pdf: Some(pdf),
+        }
+    }
+
+    /// Creates a record for a specular direction, which no other sampling
+    /// strategy can find and so has no meaningful density.
+    pub fn specular(ray: Ray, attenuation: Vector3) -> ScatterRecord {
+        ScatterRecord {
+            ray,
+            attenuation,
+            pdf: None,
         }
     }
 
@@ -24,4 +41,16 @@ impl ScatterRecord {
     pub fn attenuation(&self) -> Vector3 {
         self.attenuation
     }
+
+    /// Returns the solid angle density the direction was drawn with, or None
+    /// for specular directions.
+    #[inline]
+    pub fn pdf(&self) -> Option<f32> {
+        self.pdf
+    }
+
+    #[inline]
+    pub fn is_specular(&self) -> bool {
+        self.pdf.is_none()
+    }
 }
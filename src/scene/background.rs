@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use crate::background::{Background, EnvironmentMap};
+use crate::image::Image;
+use crate::scene::form::Form;
+use crate::scene::surface::parse_rotation;
+use crate::scene::SceneError;
+
+/// Reads the background directive: the kind of background followed by the
+/// optional rotation and intensity that apply to every kind.
+pub fn parse_background(form: &Form, base: &Path) -> Result<Background, SceneError> {
+    let (kind, settings) = match form.args.split_first() {
+        Some((kind, settings)) => (
+            Form::new(kind)?,
+            Form {
+                args: settings,
+                ..*form
+            },
+        ),
+        None => return Err(form.error("'background' expects a background")),
+    };
+    let background = match kind.name {
+        "constant" => Background::constant(kind.vector()?),
+        "gradient" => {
+            let properties = kind.properties(&["bottom", "top"])?;
+            Background::gradient(properties.vector("bottom")?, properties.vector("top")?)
+        }
+        "image" => {
+            let properties = kind.properties(&["file"])?;
+            let file = properties.require("file")?;
+            let path = base.join(file.string()?);
+            let image = Image::load_hdr(&path)
+                .map_err(|err| file.error(format!("{}: {}", path.display(), err)))?;
+            Background::image(EnvironmentMap::new(image))
+        }
+        name => return Err(kind.error(format!("unknown background '{}'", name))),
+    };
+    let properties = settings.properties(&["rotate", "intensity"])?;
+    let intensity = properties.number_or("intensity", 1.0)?;
+    if intensity < 0.0 {
+        let intensity_form = properties.require("intensity")?;
+        return Err(intensity_form.error("'intensity' must not be negative"));
+    }
+    Ok(background
+        .with_rotation(parse_rotation(&properties)?)
+        .with_intensity(intensity))
+}
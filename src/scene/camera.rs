@@ -0,0 +1,74 @@
+use crate::camera::Camera;
+use crate::scene::form::Form;
+use crate::scene::SceneError;
+use crate::vector::Vector3;
+
+/// Camera parameters read from a scene file, kept until the aspect ratio of
+/// the image is known.
+pub struct CameraSettings {
+    look_from: Vector3,
+    look_at: Vector3,
+    vup: Vector3,
+    vfov: f32,
+    aperture: f32,
+    focus_distance: f32,
+    time0: f32,
+    time1: f32,
+}
+
+impl CameraSettings {
+    #[inline]
+    pub fn time0(&self) -> f32 {
+        self.time0
+    }
+
+    #[inline]
+    pub fn time1(&self) -> f32 {
+        self.time1
+    }
+
+    pub fn build(&self, aspect: f32) -> Camera {
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.vfov,
+            aspect,
+            self.aperture,
+            self.focus_distance,
+            self.time0,
+            self.time1,
+        )
+    }
+}
+
+pub fn parse_camera(form: &Form) -> Result<CameraSettings, SceneError> {
+    let properties = form.properties(&[
+        "look-from",
+        "look-at",
+        "vup",
+        "vfov",
+        "aperture",
+        "focus-distance",
+        "shutter",
+    ])?;
+    let look_from = properties.vector("look-from")?;
+    let look_at = properties.vector("look-at")?;
+    let (time0, time1) = match properties.get("shutter") {
+        Some(shutter) => {
+            let times = shutter.numbers(2)?;
+            (times[0], times[1])
+        }
+        None => (0.0, 0.0),
+    };
+    Ok(CameraSettings {
+        look_from,
+        look_at,
+        vup: properties.vector_or("vup", Vector3::new(0.0, 1.0, 0.0))?,
+        vfov: properties.number_or("vfov", 90.0)?,
+        aperture: properties.number_or("aperture", 0.0)?,
+        focus_distance: properties.number_or("focus-distance", look_from.distance_to(look_at))?,
+        time0,
+        time1,
+    })
+}
@@ -0,0 +1,252 @@
+use std::path::Path;
+
+use crate::scene::parser::Expr;
+use crate::scene::texture::parse_texture;
+use crate::scene::SceneError;
+use crate::texture::{ConstantTexture, Texture};
+use crate::vector::Vector3;
+
+/// A list expression whose first element is an atom naming it.
+pub struct Form<'a> {
+    pub name: &'a str,
+    pub args: &'a [Expr],
+    pub line: usize,
+}
+
+impl<'a> Form<'a> {
+    pub fn new(expr: &'a Expr) -> Result<Form<'a>, SceneError> {
+        match expr {
+            Expr::List(items, line) => match items.first() {
+                Some(Expr::Atom(name, _)) => Ok(Form {
+                    name,
+                    args: &items[1..],
+                    line: *line,
+                }),
+                _ => Err(SceneError::new(*line, "expected a name after '('")),
+            },
+            Expr::Atom(atom, line) => Err(SceneError::new(
+                *line,
+                format!("expected '(' but found '{}'", atom),
+            )),
+            Expr::Str(string, line) => Err(SceneError::new(
+                *line,
+                format!("expected '(' but found \"{}\"", string),
+            )),
+        }
+    }
+
+    pub fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::new(self.line, message)
+    }
+
+    /// Reads the arguments of the form as a list of properties, rejecting
+    /// properties not named in `allowed` and properties given twice.
+    pub fn properties<S>(&self, allowed: &[S]) -> Result<Properties<'a>, SceneError>
+    where
+        S: AsRef<str>,
+    {
+        let mut entries: Vec<Form<'a>> = Vec::new();
+        for arg in self.args {
+            let property = Form::new(arg)?;
+            if !allowed.iter().any(|name| name.as_ref() == property.name) {
+                return Err(property.error(format!(
+                    "unknown property '{}' for '{}'",
+                    property.name, self.name
+                )));
+            }
+            if let Some(previous) = entries.iter().find(|e| e.name == property.name) {
+                return Err(property.error(format!(
+                    "property '{}' is already given on line {}",
+                    property.name, previous.line
+                )));
+            }
+            entries.push(property);
+        }
+        Ok(Properties {
+            owner: self.name,
+            line: self.line,
+            entries,
+        })
+    }
+
+    /// Reads the arguments of the form as exactly `count` numbers.
+    pub fn numbers(&self, count: usize) -> Result<Vec<f32>, SceneError> {
+        if self.args.len() != count {
+            return Err(self.error(format!(
+                "'{}' expects {} number{} but found {} value{}",
+                self.name,
+                count,
+                if count == 1 { "" } else { "s" },
+                self.args.len(),
+                if self.args.len() == 1 { "" } else { "s" },
+            )));
+        }
+        self.args.iter().map(number).collect()
+    }
+
+    pub fn number(&self) -> Result<f32, SceneError> {
+        Ok(self.numbers(1)?[0])
+    }
+
+    /// Reads the single argument of the form as a whole number that is not
+    /// negative.
+    pub fn integer(&self) -> Result<u64, SceneError> {
+        match self.args {
+            [Expr::Atom(atom, line)] => atom.parse().map_err(|_| {
+                SceneError::new(
+                    *line,
+                    format!("expected a whole number but found '{}'", atom),
+                )
+            }),
+            _ => Err(self.error(format!("'{}' expects a whole number", self.name))),
+        }
+    }
+
+    pub fn vector(&self) -> Result<Vector3, SceneError> {
+        let v = self.numbers(3)?;
+        Ok(Vector3::new(v[0], v[1], v[2]))
+    }
+
+    /// Reads the single argument of the form as a nested form.
+    pub fn nested(&self) -> Result<Form<'a>, SceneError> {
+        match self.args {
+            [arg] => Form::new(arg),
+            _ => Err(self.error(format!("'{}' expects a single value", self.name))),
+        }
+    }
+
+    /// Reads the single argument of the form as a quoted string.
+    pub fn string(&self) -> Result<&'a str, SceneError> {
+        match self.args {
+            [Expr::Str(string, _)] => Ok(string),
+            _ => Err(self.error(format!("'{}' expects a quoted string", self.name))),
+        }
+    }
+
+    /// Reads the arguments of the form as a texture, either written out in
+    /// full or as three numbers giving a constant color.
+    pub fn texture(&self, base: &Path) -> Result<Box<dyn Texture>, SceneError> {
+        if self.args.len() == 3 {
+            return Ok(Box::new(ConstantTexture::new(self.vector()?)));
+        }
+        parse_texture(&self.nested()?, base)
+    }
+
+    /// Reads the single argument of the form as a bare word.
+    pub fn word(&self) -> Result<&'a str, SceneError> {
+        match self.args {
+            [Expr::Atom(word, _)] => Ok(word),
+            _ => Err(self.error(format!("'{}' expects a single word", self.name))),
+        }
+    }
+}
+
+/// The properties given to a form, looked up by name.
+#[derive(Default)]
+pub struct Properties<'a> {
+    pub owner: &'a str,
+    pub line: usize,
+    entries: Vec<Form<'a>>,
+}
+
+impl<'a> Properties<'a> {
+    pub fn get(&self, name: &str) -> Option<&Form<'a>> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn require(&self, name: &str) -> Result<&Form<'a>, SceneError> {
+        self.get(name).ok_or_else(|| {
+            SceneError::new(
+                self.line,
+                format!("'{}' is missing property '{}'", self.owner, name),
+            )
+        })
+    }
+
+    pub fn number(&self, name: &str) -> Result<f32, SceneError> {
+        self.require(name)?.number()
+    }
+
+    pub fn number_or(&self, name: &str, default: f32) -> Result<f32, SceneError> {
+        self.get(name).map_or(Ok(default), |form| form.number())
+    }
+
+    pub fn vector(&self, name: &str) -> Result<Vector3, SceneError> {
+        self.require(name)?.vector()
+    }
+
+    pub fn vector_or(&self, name: &str, default: Vector3) -> Result<Vector3, SceneError> {
+        self.get(name).map_or(Ok(default), |form| form.vector())
+    }
+
+    pub fn integer_or(&self, name: &str, default: u64) -> Result<u64, SceneError> {
+        self.get(name).map_or(Ok(default), |form| form.integer())
+    }
+
+    /// Reads a property given by its name alone, such as `(flipped)`.
+    pub fn flag(&self, name: &str) -> Result<bool, SceneError> {
+        match self.get(name) {
+            Some(flag) if !flag.args.is_empty() => {
+                Err(flag.error(format!("'{}' takes no values", name)))
+            }
+            flag => Ok(flag.is_some()),
+        }
+    }
+
+    /// Reads a texture holding a single value in its red channel, which may
+    /// also be given as one number.
+    pub fn scalar_texture_or(
+        &self,
+        name: &str,
+        default: f32,
+        base: &Path,
+    ) -> Result<Box<dyn Texture>, SceneError> {
+        match self.get(name) {
+            Some(form) if form.args.len() == 1 && !matches!(form.args[0], Expr::List(..)) => {
+                let value = form.number()?;
+                Ok(Box::new(ConstantTexture::new(Vector3::new(
+                    value, value, value,
+                ))))
+            }
+            Some(form) => form.texture(base),
+            None => Ok(Box::new(ConstantTexture::new(Vector3::new(
+                default, default, default,
+            )))),
+        }
+    }
+
+    pub fn texture_or(
+        &self,
+        name: &str,
+        default: Vector3,
+        base: &Path,
+    ) -> Result<Box<dyn Texture>, SceneError> {
+        match self.get(name) {
+            Some(form) => form.texture(base),
+            None => Ok(Box::new(ConstantTexture::new(default))),
+        }
+    }
+}
+
+/// Reads a number, rejecting infinities and NaN, including numbers too large
+/// for single precision, as no property of a scene can use them.
+fn number(expr: &Expr) -> Result<f32, SceneError> {
+    let found = match expr {
+        Expr::Atom(atom, line) => match atom.parse::<f32>() {
+            Ok(value) if value.is_finite() => return Ok(value),
+            Ok(_) => {
+                return Err(SceneError::new(
+                    *line,
+                    format!("'{}' is not a finite number", atom),
+                ))
+            }
+            Err(_) => format!("'{}'", atom),
+        },
+        Expr::Str(string, _) => format!("\"{}\"", string),
+        Expr::List(..) => "'('".to_string(),
+    };
+    Err(SceneError::new(
+        expr.line(),
+        format!("expected a number but found {}", found),
+    ))
+}
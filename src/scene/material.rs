@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use crate::material::{
+    BumpMap, Dielectric, DiffuseLight, Dispersion, Isotropic, Lambertian, Material, Metal,
+    Microfacet, NormalMap, Principled,
+};
+use crate::scene::form::Form;
+use crate::scene::SceneError;
+use crate::vector::Vector3;
+
+pub fn parse_material(form: &Form, base: &Path) -> Result<Box<dyn Material>, SceneError> {
+    match form.name {
+        "lambertian" => {
+            let properties = form.properties(&["albedo"])?;
+            Ok(Box::new(Lambertian::new(
+                properties.require("albedo")?.texture(base)?,
+            )))
+        }
+        "metal" => {
+            let properties = form.properties(&["albedo", "fuzz"])?;
+            Ok(Box::new(Metal::new(
+                properties.require("albedo")?.texture(base)?,
+                properties.number_or("fuzz", 0.0)?,
+            )))
+        }
+        "dielectric" => parse_dielectric(form),
+        "microfacet" => {
+            let properties = form.properties(&["base-color", "metallic", "roughness"])?;
+            Ok(Box::new(Microfacet::new(
+                properties.require("base-color")?.texture(base)?,
+                properties.scalar_texture_or("metallic", 0.0, base)?,
+                properties.scalar_texture_or("roughness", 0.5, base)?,
+            )))
+        }
+        "principled" => parse_principled(form, base),
+        "isotropic" => {
+            let properties = form.properties(&["albedo"])?;
+            Ok(Box::new(Isotropic::new(
+                properties.require("albedo")?.texture(base)?,
+            )))
+        }
+        "diffuse-light" => {
+            let properties = form.properties(&["emit"])?;
+            Ok(Box::new(DiffuseLight::new(
+                properties.require("emit")?.texture(base)?,
+            )))
+        }
+        "normal-map" => {
+            let properties = form.properties(&["material", "normals", "strength"])?;
+            Ok(Box::new(
+                NormalMap::new(
+                    parse_material(&properties.require("material")?.nested()?, base)?,
+                    properties.require("normals")?.texture(base)?,
+                )
+                .with_strength(properties.number_or("strength", 1.0)?),
+            ))
+        }
+        "bump-map" => {
+            let properties = form.properties(&["material", "height", "scale"])?;
+            Ok(Box::new(BumpMap::new(
+                parse_material(&properties.require("material")?.nested()?, base)?,
+                properties.scalar_texture_or("height", 0.0, base)?,
+                properties.number_or("scale", 1.0)?,
+            )))
+        }
+        name => Err(form.error(format!("unknown material '{}'", name))),
+    }
+}
+
+fn parse_dielectric(form: &Form) -> Result<Box<dyn Material>, SceneError> {
+    let properties = form.properties(&[
+        "ri",
+        "cauchy",
+        "sellmeier",
+        "roughness",
+        "absorption",
+        "thin-walled",
+    ])?;
+    // The refractive index is either one number or the coefficients of a
+    // formula giving it at every wavelength.
+    let indices: Vec<&Form> = ["ri", "cauchy", "sellmeier"]
+        .iter()
+        .filter_map(|name| properties.get(name))
+        .collect();
+    let material = match indices.as_slice() {
+        [] => Dielectric::new(properties.number("ri")?),
+        [index] if index.name == "ri" => Dielectric::new(index.number()?),
+        [index] if index.name == "cauchy" => {
+            let coefficients = index.numbers(2)?;
+            Dielectric::dispersive(Dispersion::Cauchy {
+                a: coefficients[0],
+                b: coefficients[1],
+            })
+        }
+        [index] => {
+            let coefficients = index.numbers(6)?;
+            Dielectric::dispersive(Dispersion::Sellmeier {
+                b: [coefficients[0], coefficients[1], coefficients[2]],
+                c: [coefficients[3], coefficients[4], coefficients[5]],
+            })
+        }
+        [first, second, ..] => {
+            return Err(second.error(format!(
+                "'{}' cannot be given with '{}'",
+                second.name, first.name
+            )));
+        }
+    };
+
+    let roughness = properties.number_or("roughness", 0.0)?;
+    if roughness < 0.0 {
+        let roughness_form = properties.require("roughness")?;
+        return Err(roughness_form.error("'roughness' must not be negative"));
+    }
+    let absorption = properties.vector_or("absorption", Vector3::default())?;
+    if absorption.x() < 0.0 || absorption.y() < 0.0 || absorption.z() < 0.0 {
+        let absorption_form = properties.require("absorption")?;
+        return Err(absorption_form.error("'absorption' must not be negative"));
+    }
+    let material = material
+        .with_roughness(roughness)
+        .with_absorption(absorption);
+    if properties.flag("thin-walled")? {
+        Ok(Box::new(material.thin_walled()))
+    } else {
+        Ok(Box::new(material))
+    }
+}
+
+fn parse_principled(form: &Form, base: &Path) -> Result<Box<dyn Material>, SceneError> {
+    let properties = form.properties(&[
+        "base-color",
+        "subsurface",
+        "metallic",
+        "specular",
+        "roughness",
+        "sheen",
+        "sheen-tint",
+        "clearcoat",
+        "clearcoat-roughness",
+        "transmission",
+        "ior",
+    ])?;
+    let ior = properties.number_or("ior", 1.5)?;
+    if ior <= 0.0 {
+        let ior_form = properties.require("ior")?;
+        return Err(ior_form.error("'ior' must be greater than zero"));
+    }
+    let scalar = |name, default| properties.scalar_texture_or(name, default, base);
+    Ok(Box::new(
+        Principled::new(properties.require("base-color")?.texture(base)?)
+            .with_subsurface(scalar("subsurface", 0.0)?)
+            .with_metallic(scalar("metallic", 0.0)?)
+            .with_specular(scalar("specular", 0.5)?)
+            .with_roughness(scalar("roughness", 0.5)?)
+            .with_sheen(scalar("sheen", 0.0)?)
+            .with_sheen_tint(scalar("sheen-tint", 0.5)?)
+            .with_clearcoat(scalar("clearcoat", 0.0)?)
+            .with_clearcoat_roughness(scalar("clearcoat-roughness", 0.03)?)
+            .with_transmission(scalar("transmission", 0.0)?)
+            .with_ior(ior),
+    ))
+}
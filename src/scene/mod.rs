@@ -0,0 +1,664 @@
+//! Loads scenes from a small s-expression based text format.
+//!
+//! A scene file holds one `camera` and any number of surfaces. Properties are
+//! written as `(name values...)`, materials and textures nest inside the
+//! properties that take them, and `#` or `;` start a comment.
+//!
+//! ```text
+//! (camera
+//!     (look-from 13 2 3)
+//!     (look-at 0 0 0)
+//!     (vup 0 1 0)              ; optional, defaults to 0 1 0
+//!     (vfov 20)                ; optional, defaults to 90
+//!     (aperture 0.1)           ; optional, defaults to 0
+//!     (focus-distance 10)      ; optional, defaults to the look-at distance
+//!     (shutter 0 1))           ; optional, defaults to 0 0
+//!
+//! (sphere
+//!     (center 0 -1000 0)
+//!     (radius 1000)
+//!     (material (lambertian (albedo (checker (constant 0.2 0.3 0.1)
+//!                                            (constant 0.9 0.9 0.9))))))
+//!
+//! (moving-sphere
+//!     (center0 0 1 0) (center1 0 1.5 0) (time0 0) (time1 1) (radius 0.5)
+//!     (material (metal (albedo 0.7 0.6 0.5) (fuzz 0.1))))
+//!
+//! (sphere (center 4 1 0) (radius 1) (material (dielectric (ri 1.5))))
+//!
+//! (sphere (center 6 1 0) (radius 1)
+//!     (material (dielectric
+//!         (ri 1.5)                   ; or (cauchy a b) or (sellmeier b1 b2 b3 c1 c2 c3)
+//!         (roughness 0.2)            ; optional, frosts the glass, defaults to 0
+//!         (absorption 0.1 0.4 0.8)   ; optional, attenuation coefficients per unit of distance
+//!         (thin-walled))))           ; optional, a sheet with no inside
+//!
+//! (triangle (a 0 0 0) (b 1 0 0) (c 0 1 0) (material (lambertian (albedo 1 1 1))))
+//!
+//! (xz-rect                     ; or xy-rect or yz-rect
+//!     (x 0 555) (z 0 555)      ; the ranges spanned along the two axes
+//!     (k 0)                    ; the position along the third axis
+//!     (material (lambertian (albedo 0.73 0.73 0.73)))
+//!     (flipped))               ; optional, turns the normal towards -y
+//!
+//! (box (min 130 0 65) (max 295 165 230) (material (lambertian (albedo 1 1 1))))
+//!
+//! (transformed                 ; places any surface, applying the optional
+//!     (surface (box (min 0 0 0) (max 1 1 1) (material (metal (albedo 0.8 0.8 0.8)))))
+//!     (scale 165 330 165)      ;   scale first, defaults to 1 1 1
+//!     (rotate 0 1 0 15)        ;   then rotation by degrees about an axis
+//!     (translate 265 0 295))   ;   and last translation, defaults to 0 0 0
+//!
+//! (mesh
+//!     (file "model.obj")                             ; relative to the scene
+//!     (material (lambertian (albedo 0.8 0.8 0.8)))   ; for unlisted groups
+//!     (materials (glass (dielectric (ri 1.5)))))     ; by usemtl group name
+//! ```
+//!
+//! A participating medium such as fog or smoke fills the inside of a closed
+//! boundary surface with a uniform density of particles that scatter light
+//! in all directions:
+//!
+//! ```text
+//! (constant-medium
+//!     (boundary (sphere (center 0 1 0) (radius 1) (material (dielectric (ri 1.5)))))
+//!     (density 0.5)            ; chance of scattering per unit of distance
+//!     (albedo 0.9 0.9 0.9))    ; optional, defaults to 1 1 1
+//! ```
+//!
+//! The boundary only gives the shape of the medium; to also see a surface
+//! there, add the boundary surface to the scene as well.
+//!
+//! The refractive index of a dispersive dielectric follows Cauchy's equation
+//! `n = a + b / λ²` or the Sellmeier equation `n² = 1 + Σ b λ² / (λ² - c)`,
+//! with λ in micrometres as in published tables of coefficients. Only
+//! renders with `--spectral` split light into its colors; others use the
+//! index at 589.3 nanometres.
+//!
+//! The optional `background` gives the light arriving from outside the scene,
+//! either `(constant r g b)`, `(gradient (bottom r g b) (top r g b))` or a
+//! panorama in the equirectangular projection loaded from a Radiance HDR
+//! file, which any background may turn and scale,
+//!
+//! ```text
+//! (background
+//!     (image (file "studio.hdr"))  ; relative to the scene
+//!     (rotate 0 1 0 90)            ; optional, by degrees about an axis
+//!     (intensity 2))               ; optional, scales the radiance, defaults to 1
+//! ```
+//!
+//! and defaults to a white to blue sky. The middle of an unrotated panorama
+//! lies along -z. Backgrounds that are not black are sampled directly as
+//! lights, panoramas in proportion to the brightness of their pixels.
+//!
+//! Light sources use the `(diffuse-light (emit texture))` material. Spheres,
+//! moving spheres, triangles and rectangles with that material are also
+//! sampled directly when shading other surfaces, while lights of other
+//! shapes are only found by paths that happen to hit them.
+//!
+//! Physically based materials authored in the metallic-roughness workflow of
+//! tools such as Blender and Substance use the `microfacet` material, whose
+//! metallic and roughness are either textures, read from their red channel,
+//! or single numbers:
+//!
+//! ```text
+//! (microfacet
+//!     (base-color 0.9 0.6 0.2) ; the diffuse color, or the tint of metals
+//!     (metallic 1)             ; optional, defaults to 0
+//!     (roughness 0.3))         ; optional, defaults to 0.5
+//! ```
+//!
+//! The `principled` material in the style of the Disney BSDF covers
+//! plastics, metals, coated surfaces, fabrics and glass in one. Every
+//! parameter but the base color and index of refraction is likewise a
+//! texture or a number between 0 and 1:
+//!
+//! ```text
+//! (principled
+//!     (base-color 0.8 0.1 0.1)
+//!     (subsurface 0)           ; optional, flattens the diffuse, defaults to 0
+//!     (metallic 0)             ; optional, defaults to 0
+//!     (specular 0.5)           ; optional, dielectric reflectance, defaults to 0.5
+//!     (roughness 0.5)          ; optional, defaults to 0.5
+//!     (sheen 0)                ; optional, cloth-like grazing sheen, defaults to 0
+//!     (sheen-tint 0.5)         ; optional, defaults to 0.5
+//!     (clearcoat 0)            ; optional, a varnish layer, defaults to 0
+//!     (clearcoat-roughness 0.03)  ; optional, defaults to 0.03
+//!     (transmission 0)         ; optional, 1 for glass, defaults to 0
+//!     (ior 1.5))               ; optional, of the glass, defaults to 1.5
+//! ```
+//!
+//! Any material may be given detail finer than its surface by wrapping it in
+//! a `normal-map` or `bump-map`, which tilt the normal it is shaded with:
+//!
+//! ```text
+//! (normal-map
+//!     (material (lambertian (albedo 0.8 0.8 0.8)))
+//!     (normals (image (file "bricks-normal.png") (color-space linear)))
+//!     (strength 1))            ; optional, scales the tilt, defaults to 1
+//!
+//! (bump-map
+//!     (material (metal (albedo 0.9 0.9 0.9) (fuzz 0.1)))
+//!     (height (noise (scale 8)))  ; read from the red channel
+//!     (scale 0.02))            ; optional, the height of 1, defaults to 1
+//! ```
+//!
+//! Normal maps follow the OpenGL convention, with green along v, and are
+//! usually loaded in the linear color space as they hold directions rather
+//! than colors. The tilt follows how u and v run across the surface.
+//!
+//! A texture is `(constant r g b)`, `(checker texture texture)`, an image
+//! loaded from a PNG file relative to the scene,
+//!
+//! ```text
+//! (image
+//!     (file "wood.png")
+//!     (color-space srgb)       ; optional, srgb or linear, defaults to srgb
+//!     (filter bilinear)        ; optional, nearest or bilinear, defaults to bilinear
+//!     (addressing wrap))       ; optional, wrap, clamp or mirror, defaults to wrap
+//! ```
+//!
+//! or a pattern made from seeded Perlin noise,
+//!
+//! ```text
+//! (marble                      ; or noise or wood
+//!     (seed 3)                 ; optional, defaults to 0
+//!     (scale 4)                ; optional, features per unit, defaults to 1
+//!     (octaves 7)              ; optional, the turbulence settings, default to
+//!     (lacunarity 2)           ;   7 octaves, each at twice the frequency and
+//!     (gain 0.5)               ;   half the amplitude of the one before
+//!     (strength 10)            ; optional, how far turbulence moves the pattern
+//!     (vein 0.2 0.2 0.25)      ; optional, the two textures blended by the
+//!     (base 0.9 0.9 0.88))     ;   pattern, low and high for noise and early
+//!                              ;   and late for wood
+//! ```
+//!
+//! A property expecting a texture also accepts three numbers as a shorthand
+//! for a constant texture.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::surface::{Bvh, Surface};
+
+use self::background::parse_background;
+use self::camera::{parse_camera, CameraSettings};
+use self::form::Form;
+use self::surface::{
+    is_sampled_light, parse_definition, parse_instance, parse_surface, Definition,
+};
+
+mod background;
+mod camera;
+mod form;
+mod material;
+mod parser;
+mod surface;
+mod texture;
+
+/// A world to render together with the camera viewing it.
+#[derive(Debug)]
+pub struct Scene {
+    world: Bvh,
+    lights: Vec<Box<dyn Surface>>,
+    camera: Camera,
+    background: Background,
+}
+
+impl Scene {
+    pub fn new(world: Bvh, camera: Camera) -> Scene {
+        Scene {
+            world,
+            lights: Vec::new(),
+            camera,
+            background: Background::default(),
+        }
+    }
+
+    /// Gives the surfaces to sample directly as light sources. They are only
+    /// used to choose directions, so the emitted light is always taken from
+    /// the world, which must contain matching emitters.
+    pub fn with_lights(self, lights: Vec<Box<dyn Surface>>) -> Scene {
+        Scene { lights, ..self }
+    }
+
+    /// Replaces the default sky with the given background.
+    pub fn with_background(self, background: Background) -> Scene {
+        Scene { background, ..self }
+    }
+
+    /// Reads and parses the scene file at the path, framing the camera for
+    /// an image with the given aspect ratio.
+    pub fn load<T>(path: T, aspect: f32) -> Result<Scene, SceneError>
+    where
+        T: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| SceneError::without_line(format!("cannot read file: {}", err)))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::parse_in(&source, aspect, base)
+    }
+
+    /// Parses the text of a scene file, framing the camera for an image with
+    /// the given aspect ratio. Files named by the scene are found relative to
+    /// the current directory.
+    pub fn parse(source: &str, aspect: f32) -> Result<Scene, SceneError> {
+        Scene::parse_in(source, aspect, Path::new(""))
+    }
+
+    fn parse_in(source: &str, aspect: f32, base: &Path) -> Result<Scene, SceneError> {
+        let mut camera: Option<(CameraSettings, usize)> = None;
+        let mut background: Option<(Background, usize)> = None;
+        let mut surfaces: Vec<Box<dyn Surface>> = Vec::new();
+        let mut lights: Vec<Box<dyn Surface>> = Vec::new();
+        let mut definitions: Vec<Definition> = Vec::new();
+        let exprs = parser::parse(source)?;
+        for expr in &exprs {
+            let form = Form::new(expr)?;
+            match form.name {
+                "camera" => {
+                    if let Some((_, line)) = camera {
+                        return Err(
+                            form.error(format!("camera is already defined on line {}", line))
+                        );
+                    }
+                    camera = Some((parse_camera(&form)?, form.line));
+                }
+                "background" => {
+                    if let Some((_, line)) = background {
+                        return Err(
+                            form.error(format!("background is already defined on line {}", line))
+                        );
+                    }
+                    background = Some((parse_background(&form, base)?, form.line));
+                }
+                "define" => {
+                    let definition = parse_definition(&form, base)?;
+                    if let Some(previous) =
+                        definitions.iter().find(|d| d.name() == definition.name())
+                    {
+                        return Err(form.error(format!(
+                            "'{}' is already defined on line {}",
+                            definition.name(),
+                            previous.line()
+                        )));
+                    }
+                    definitions.push(definition);
+                }
+                "instance" => {
+                    let (instance, light) = parse_instance(&form, &definitions, base)?;
+                    if light {
+                        let instance: Arc<dyn Surface> = Arc::new(instance);
+                        lights.push(Box::new(Arc::clone(&instance)));
+                        surfaces.push(Box::new(instance));
+                    } else {
+                        surfaces.push(Box::new(instance));
+                    }
+                }
+                _ => {
+                    let surface = parse_surface(&form, base)?;
+                    if is_sampled_light(&form) {
+                        // The world and the lights share the one surface.
+                        let surface: Arc<dyn Surface> = Arc::from(surface);
+                        lights.push(Box::new(Arc::clone(&surface)));
+                        surfaces.push(Box::new(surface));
+                    } else {
+                        surfaces.push(surface);
+                    }
+                }
+            }
+        }
+        let (settings, _) =
+            camera.ok_or_else(|| SceneError::without_line("scene has no camera"))?;
+        let world = Bvh::new(surfaces, settings.time0(), settings.time1());
+        let scene = Scene::new(world, settings.build(aspect)).with_lights(lights);
+        Ok(match background {
+            Some((background, _)) => scene.with_background(background),
+            None => scene,
+        })
+    }
+
+    #[inline]
+    pub fn world(&self) -> &Bvh {
+        &self.world
+    }
+
+    /// Returns the surfaces sampled directly as light sources.
+    #[inline]
+    pub fn lights(&self) -> &Vec<Box<dyn Surface>> {
+        &self.lights
+    }
+
+    #[inline]
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    #[inline]
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+}
+
+/// An error found while loading a scene, reported against the line of the
+/// scene file it was found on, if it is tied to one.
+#[derive(Debug)]
+pub struct SceneError {
+    line: Option<usize>,
+    message: String,
+}
+
+impl SceneError {
+    pub fn new(line: usize, message: impl Into<String>) -> SceneError {
+        SceneError {
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    /// Creates an error about the scene as a whole rather than any one line
+    /// of it.
+    pub fn without_line(message: impl Into<String>) -> SceneError {
+        SceneError {
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    #[inline]
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    fn error_of(source: &str) -> SceneError {
+        Scene::parse(source, 1.0).unwrap_err()
+    }
+
+    #[test]
+    fn parses_example_scene() {
+        let source = include_str!("../../scenes/three_spheres.scene");
+        let scene = Scene::parse(source, 1.5).unwrap();
+        assert!(scene.world().bounding_box(0.0, 1.0).is_some());
+    }
+
+    #[test]
+    fn loads_mesh_relative_to_scene() {
+        let scene = Scene::load("scenes/pyramid.scene", 1.0).unwrap();
+        let bounds = scene.world().bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.max().y(), 1.5);
+        assert!(Scene::load("scenes/textured.scene", 1.0).is_ok());
+    }
+
+    #[test]
+    fn loads_image_texture_relative_to_scene() {
+        let base = std::env::temp_dir().join(format!("scene-texture-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let file = fs::File::create(base.join("gray.png")).unwrap();
+        let mut image = crate::image::Image::new(1, 1);
+        image.set_pixel(0, 0, Vector3::new(0.5, 0.5, 0.5));
+        image
+            .write_png(file, &crate::tonemap::ToneMap::default())
+            .unwrap();
+
+        let camera = "(camera (look-from 0 0 0) (look-at 0 0 -1))";
+        let sphere = |texture: &str| {
+            format!(
+                "{}\n(sphere (center 0 0 -2) (radius 1)\n  (material (lambertian (albedo {}))))",
+                camera, texture
+            )
+        };
+        let source = sphere("(image (file \"gray.png\") (filter nearest) (addressing clamp))");
+        assert!(Scene::parse_in(&source, 1.0, &base).is_ok());
+        let source = sphere("(image (file \"gray.png\") (filter trilinear))");
+        let err = Scene::parse_in(&source, 1.0, &base).unwrap_err();
+        assert_eq!(
+            (err.line(), err.message()),
+            (Some(3), "unknown filter 'trilinear'")
+        );
+        let source = sphere("(image (file \"missing.png\"))");
+        let err = Scene::parse_in(&source, 1.0, &base).unwrap_err();
+        assert_eq!(err.line(), Some(3));
+        assert!(err.message().contains("missing.png"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn reads_noise_textures() {
+        assert!(Scene::load("scenes/procedural.scene", 1.0).is_ok());
+        let sphere = |texture: &str| {
+            format!(
+                "(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (lambertian (albedo {}))))",
+                texture
+            )
+        };
+        let message = |texture| error_of(&sphere(texture)).message().to_string();
+        assert_eq!(
+            message("(wood (seed -1))"),
+            "expected a whole number but found '-1'"
+        );
+        assert_eq!(
+            message("(noise (octaves 0))"),
+            "'octaves' must be between 1 and 32"
+        );
+        assert_eq!(
+            message("(noise (strength 2))"),
+            "unknown property 'strength' for 'noise'"
+        );
+    }
+
+    #[test]
+    fn reads_constant_medium() {
+        assert!(Scene::load("scenes/fog.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(constant-medium\n  (boundary (sphere (center 0 0 -2) (radius 1) (material (isotropic (albedo 1 1 1)))))\n  (density 0))");
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(err.message(), "'density' must be greater than zero");
+    }
+
+    #[test]
+    fn reads_rects_and_boxes() {
+        let scene = Scene::load("scenes/cornell.scene", 1.0).unwrap();
+        assert_eq!(scene.lights().len(), 1);
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(xy-rect (x 0 1) (z 0 1) (k -1)\n  (material (lambertian (albedo 1 1 1))))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "unknown property 'z' for 'xy-rect'");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(yz-rect (y 0 1) (z 0 1) (k -1) (flipped yes)\n  (material (lambertian (albedo 1 1 1))))");
+        assert_eq!(err.message(), "'flipped' takes no values");
+    }
+
+    #[test]
+    fn reads_transformed_surfaces() {
+        let source = "(camera (look-from 0 0 0) (look-at 0 0 -1))\n(transformed\n  (surface (sphere (center 0 0 0) (radius 1) (material (diffuse-light (emit 1 1 1)))))\n  (scale 2 1 1) (rotate 0 0 1 90) (translate 0 0 -5))";
+        let scene = Scene::parse(source, 1.0).unwrap();
+        assert_eq!(scene.lights().len(), 1);
+        let bounds = scene.world().bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.min() - Vector3::new(-1.0, -2.0, -6.0)).length() < 1e-5);
+        assert!((bounds.max() - Vector3::new(1.0, 2.0, -4.0)).length() < 1e-5);
+
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(transformed (scale 1 0 1)\n  (surface (sphere (center 0 0 0) (radius 1) (material (isotropic (albedo 1 1 1))))))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "'scale' factors must not be zero");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(transformed (scale 1e-20 1e-20 1e-20)\n  (surface (sphere (center 0 0 0) (radius 1) (material (isotropic (albedo 1 1 1))))))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "'transformed' transform cannot be inverted");
+    }
+
+    #[test]
+    fn reads_instances() {
+        let scene = Scene::load("scenes/instances.scene", 1.0).unwrap();
+        assert!(scene.lights().is_empty());
+        let source = "(camera (look-from 0 0 0) (look-at 0 0 -1))\n(define ball (sphere (center 0 0 0) (radius 1) (material (isotropic (albedo 1 1 1)))))\n(instance (of ball) (translate 0 0 -5))\n(instance (of ball) (translate 0 0 -9) (material (diffuse-light (emit 1 1 1))))";
+        let scene = Scene::parse(source, 1.0).unwrap();
+        assert_eq!(scene.lights().len(), 1);
+        let bounds = scene.world().bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.min() - Vector3::new(-1.0, -1.0, -10.0)).length() < 1e-5);
+
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(instance (of ball))\n(define ball (sphere (center 0 0 0) (radius 1) (material (isotropic (albedo 1 1 1)))))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "'ball' is not defined");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(define ball (sphere (center 0 0 0) (radius 1) (material (isotropic (albedo 1 1 1)))))\n(define ball (sphere (center 0 0 0) (radius 2) (material (isotropic (albedo 1 1 1)))))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "'ball' is already defined on line 2");
+    }
+
+    #[test]
+    fn reads_microfacet_material() {
+        assert!(Scene::load("scenes/materials.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (microfacet (base-color 1 1 1) (roughness 0.2 0.5))))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "'roughness' expects a single value");
+    }
+
+    #[test]
+    fn reads_principled_material() {
+        assert!(Scene::load("scenes/principled.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (principled (base-color 1 1 1)\n    (ior 0))))");
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(err.message(), "'ior' must be greater than zero");
+    }
+
+    #[test]
+    fn reads_glass() {
+        assert!(Scene::load("scenes/glass.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (dielectric (ri 1.5)\n    (absorption 0 -1 0))))");
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(err.message(), "'absorption' must not be negative");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (dielectric (ri 1.5) (thin-walled 1))))");
+        assert_eq!(err.message(), "'thin-walled' takes no values");
+    }
+
+    #[test]
+    fn reads_dispersive_glass() {
+        assert!(Scene::load("scenes/prism.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (dielectric (ri 1.5)\n    (cauchy 1.5 0.004))))");
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(err.message(), "'cauchy' cannot be given with 'ri'");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (dielectric (sellmeier 1 2 3))))");
+        assert_eq!(
+            err.message(),
+            "'sellmeier' expects 6 numbers but found 3 values"
+        );
+    }
+
+    #[test]
+    fn reads_normal_and_bump_maps() {
+        assert!(Scene::load("scenes/bumps.scene", 1.0).is_ok());
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (normal-map (normals 0.5 0.5 1))))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "'normal-map' is missing property 'material'");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 -2) (radius 1)\n  (material (bump-map (material (lambertian (albedo 1 1 1)))\n    (height (image (file \"missing.png\"))))))");
+        assert_eq!(err.line(), Some(4));
+    }
+
+    #[test]
+    fn reads_background() {
+        let scene = Scene::load("scenes/lamp.scene", 1.0).unwrap();
+        assert_eq!(
+            *scene.background(),
+            Background::constant(Vector3::default())
+        );
+        assert_eq!(scene.lights().len(), 1);
+        let scene = Scene::load("scenes/three_spheres.scene", 1.0).unwrap();
+        assert_eq!(*scene.background(), Background::default());
+        assert!(scene.lights().is_empty());
+        // Any background may be turned and scaled, here upside down.
+        let scene = Scene::parse(
+            "(camera (look-from 0 0 0) (look-at 0 0 -1))\n(background (gradient (bottom 0 0 0) (top 1 1 1))\n  (rotate 1 0 0 180) (intensity 2))",
+            1.0,
+        )
+        .unwrap();
+        let down = scene.background().value(Vector3::new(0.0, -1.0, 0.0));
+        assert!((down - Vector3::new(2.0, 2.0, 2.0)).length() < 1e-5);
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(background (constant 1 1 1) (turn 0 1 0 90))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "unknown property 'turn' for 'background'");
+    }
+
+    #[test]
+    fn reads_environment_map() {
+        let scene = Scene::load("scenes/sky.scene", 1.0).unwrap();
+        // Straight down the panorama shows the dim ground.
+        let ground = scene.background().value(Vector3::new(0.0, -1.0, 0.0));
+        assert!(ground.x() > 0.0 && ground.x() < 1.0, "{:?}", ground);
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(background (image (file \"scenes/sky.hdr\"))\n  (intensity -1))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "'intensity' must not be negative");
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(background (image (file \"scenes/sky.scene\")))");
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.message(), "scenes/sky.scene: not a Radiance HDR file");
+    }
+
+    #[test]
+    fn reports_line_of_bad_value() {
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n\n(sphere (center 0 0 x) (radius 1)\n  (material (dielectric (ri 1.5))))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "expected a number but found 'x'");
+    }
+
+    #[test]
+    fn reports_line_of_unknown_names() {
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 0) (radius 1)\n  (material (lamberian (albedo 1 1 1))))");
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.message(), "unknown material 'lamberian'");
+    }
+
+    #[test]
+    fn reports_missing_and_unbalanced_input() {
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 0 0)\n  (material (dielectric (ri 1.5))))");
+        assert_eq!(
+            (err.line(), err.message()),
+            (Some(2), "'sphere' is missing property 'radius'")
+        );
+        let err = error_of("(camera (look-from 0 0 0)\n (look-at 0 0 -1)");
+        assert_eq!(
+            (err.line(), err.message()),
+            (Some(1), "'(' is never closed")
+        );
+        let err = error_of("(sphere (center 0 0 0) (radius 1) (material (dielectric (ri 1.5))))");
+        assert_eq!((err.line(), err.message()), (None, "scene has no camera"));
+        assert_eq!(err.to_string(), "scene has no camera");
+        for number in &["nan", "inf", "-infinity", "1e39"] {
+            let err = error_of(&format!(
+                "(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere (center 0 {} 0) (radius 1)\n  (material (dielectric (ri 1.5))))",
+                number
+            ));
+            assert_eq!(err.line(), Some(2));
+            assert_eq!(
+                err.message(),
+                format!("'{}' is not a finite number", number)
+            );
+        }
+        let err = error_of("(camera (look-from 0 0 0) (look-at 0 0 -1))\n(sphere)");
+        assert_eq!(
+            err.to_string(),
+            "line 2: 'sphere' is missing property 'center'"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::scene::SceneError;
+
+/// A node of a parsed scene description. Every node remembers the line on
+/// which it started so that errors can point back into the source.
+#[derive(Debug)]
+pub enum Expr {
+    Atom(String, usize),
+    Str(String, usize),
+    List(Vec<Expr>, usize),
+}
+
+impl Expr {
+    #[inline]
+    pub fn line(&self) -> usize {
+        match *self {
+            Expr::Atom(_, line) | Expr::Str(_, line) | Expr::List(_, line) => line,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Atom(String),
+    Str(String),
+}
+
+/// Parses the text of a scene description into its top level expressions.
+pub fn parse(source: &str) -> Result<Vec<Expr>, SceneError> {
+    let tokens = tokenize(source)?;
+    let mut stack: Vec<(Vec<Expr>, usize)> = Vec::new();
+    let mut top = Vec::new();
+    for (token, line) in tokens {
+        match token {
+            Token::Open => stack.push((Vec::new(), line)),
+            Token::Close => {
+                let (items, start) = stack
+                    .pop()
+                    .ok_or_else(|| SceneError::new(line, "unexpected ')'"))?;
+                push(&mut stack, &mut top, Expr::List(items, start));
+            }
+            Token::Atom(atom) => push(&mut stack, &mut top, Expr::Atom(atom, line)),
+            Token::Str(string) => push(&mut stack, &mut top, Expr::Str(string, line)),
+        }
+    }
+    if let Some((_, start)) = stack.pop() {
+        return Err(SceneError::new(start, "'(' is never closed"));
+    }
+    Ok(top)
+}
+
+fn push(stack: &mut [(Vec<Expr>, usize)], top: &mut Vec<Expr>, expr: Expr) {
+    match stack.last_mut() {
+        Some((items, _)) => items.push(expr),
+        None => top.push(expr),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, SceneError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '(' => tokens.push((Token::Open, line)),
+            ')' => tokens.push((Token::Close, line)),
+            '#' | ';' => {
+                // Comments run to the end of the line.
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '"' => {
+                let start = line;
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(SceneError::new(start, "string is never closed"));
+                        }
+                        Some(c) => string.push(c),
+                    }
+                }
+                tokens.push((Token::Str(string), start));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Atom(atom), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
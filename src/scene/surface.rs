@@ -0,0 +1,293 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::obj::ObjModel;
+use crate::scene::form::{Form, Properties};
+use crate::scene::material::parse_material;
+use crate::scene::parser::Expr;
+use crate::scene::SceneError;
+use crate::surface::{
+    BoxSurface, ConstantMedium, Instance, MovingSphere, Sphere, Surface, Transformed, Triangle,
+    XYRect, XZRect, YZRect,
+};
+use crate::vector::{Matrix4, Vector3};
+
+pub fn parse_surface(form: &Form, base: &Path) -> Result<Box<dyn Surface>, SceneError> {
+    match form.name {
+        "sphere" => {
+            let properties = form.properties(&["center", "radius", "material"])?;
+            Ok(Box::new(Sphere::new(
+                properties.vector("center")?,
+                properties.number("radius")?,
+                parse_material(&properties.require("material")?.nested()?, base)?,
+            )))
+        }
+        "moving-sphere" => {
+            let properties =
+                form.properties(&["center0", "center1", "time0", "time1", "radius", "material"])?;
+            Ok(Box::new(MovingSphere::new(
+                properties.vector("center0")?,
+                properties.vector("center1")?,
+                properties.number("time0")?,
+                properties.number("time1")?,
+                properties.number("radius")?,
+                parse_material(&properties.require("material")?.nested()?, base)?,
+            )))
+        }
+        "triangle" => {
+            let properties = form.properties(&["a", "b", "c", "material"])?;
+            Ok(Box::new(Triangle::new(
+                properties.vector("a")?,
+                properties.vector("b")?,
+                properties.vector("c")?,
+                parse_material(&properties.require("material")?.nested()?, base)?,
+            )))
+        }
+        "xy-rect" | "xz-rect" | "yz-rect" => parse_rect(form, base),
+        "box" => {
+            let properties = form.properties(&["min", "max", "material"])?;
+            Ok(Box::new(BoxSurface::new(
+                properties.vector("min")?,
+                properties.vector("max")?,
+                parse_material(&properties.require("material")?.nested()?, base)?,
+            )))
+        }
+        "mesh" => parse_mesh(form, base),
+        "transformed" => {
+            let properties = form.properties(&["surface", "scale", "rotate", "translate"])?;
+            Ok(Box::new(Transformed::new(
+                parse_surface(&properties.require("surface")?.nested()?, base)?,
+                parse_transform(&properties)?,
+            )))
+        }
+        "constant-medium" => {
+            let properties = form.properties(&["boundary", "density", "albedo"])?;
+            let density = properties.require("density")?;
+            if density.number()? <= 0.0 {
+                return Err(density.error("'density' must be greater than zero"));
+            }
+            Ok(Box::new(ConstantMedium::new(
+                parse_surface(&properties.require("boundary")?.nested()?, base)?,
+                density.number()?,
+                properties.texture_or("albedo", Vector3::new(1.0, 1.0, 1.0), base)?,
+            )))
+        }
+        name => Err(form.error(format!("unknown surface '{}'", name))),
+    }
+}
+
+/// Geometry given a name by `define` to be placed by instances.
+pub struct Definition<'a> {
+    name: &'a str,
+    line: usize,
+    geometry: Arc<dyn Surface>,
+    sampled_shape: bool,
+    light: bool,
+}
+
+impl<'a> Definition<'a> {
+    #[inline]
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+pub fn parse_definition<'a>(form: &Form<'a>, base: &Path) -> Result<Definition<'a>, SceneError> {
+    match form.args {
+        [Expr::Atom(name, _), surface] => {
+            let surface = Form::new(surface)?;
+            Ok(Definition {
+                name,
+                line: form.line,
+                geometry: Arc::from(parse_surface(&surface, base)?),
+                sampled_shape: is_sampled_shape(&surface),
+                light: is_sampled_light(&surface),
+            })
+        }
+        _ => Err(form.error("'define' expects a name and a surface")),
+    }
+}
+
+/// Reads an instance of earlier defined geometry, returning it together with
+/// whether it should also be sampled as a light.
+pub fn parse_instance(
+    form: &Form,
+    definitions: &[Definition],
+    base: &Path,
+) -> Result<(Instance, bool), SceneError> {
+    let properties = form.properties(&["of", "material", "scale", "rotate", "translate"])?;
+    let of = properties.require("of")?;
+    let name = of.word()?;
+    let definition = definitions
+        .iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| of.error(format!("'{}' is not defined", name)))?;
+    let instance = Instance::new(definition.geometry.clone(), parse_transform(&properties)?);
+    Ok(match properties.get("material") {
+        Some(material) => {
+            let material = material.nested()?;
+            let light = definition.sampled_shape && material.name == "diffuse-light";
+            let material = Arc::from(parse_material(&material, base)?);
+            (instance.with_material(material), light)
+        }
+        None => (instance, definition.light),
+    })
+}
+
+/// Reads the optional `scale`, `rotate` and `translate` properties as the
+/// transform applying them in that order.
+fn parse_transform(properties: &Properties) -> Result<Matrix4, SceneError> {
+    let scale = match properties.get("scale") {
+        Some(scale) => {
+            let factors = scale.vector()?;
+            if factors.x() == 0.0 || factors.y() == 0.0 || factors.z() == 0.0 {
+                return Err(scale.error("'scale' factors must not be zero"));
+            }
+            factors
+        }
+        None => Vector3::new(1.0, 1.0, 1.0),
+    };
+    let translation = properties.vector_or("translate", Vector3::default())?;
+    let transform =
+        Matrix4::translation(translation) * parse_rotation(properties)? * Matrix4::scaling(scale);
+    // Factors too small for single precision collapse space as zero does.
+    if transform.inverse().is_none() {
+        return Err(SceneError::new(
+            properties.line,
+            format!("'{}' transform cannot be inverted", properties.owner),
+        ));
+    }
+    Ok(transform)
+}
+
+/// Reads the optional `rotate` property, an axis and an angle in degrees.
+pub fn parse_rotation(properties: &Properties) -> Result<Matrix4, SceneError> {
+    match properties.get("rotate") {
+        Some(rotate) => {
+            let r = rotate.numbers(4)?;
+            let axis = Vector3::new(r[0], r[1], r[2]);
+            if axis.squared_norm() == 0.0 {
+                return Err(rotate.error("'rotate' axis must not be zero"));
+            }
+            Ok(Matrix4::rotation(axis, r[3]))
+        }
+        None => Ok(Matrix4::identity()),
+    }
+}
+
+/// Reads a rectangle perpendicular to one axis, with the range it spans along
+/// each of the other two axes and its position `k` along the first.
+fn parse_rect(form: &Form, base: &Path) -> Result<Box<dyn Surface>, SceneError> {
+    let (a, b) = match form.name {
+        "xy-rect" => ("x", "y"),
+        "xz-rect" => ("x", "z"),
+        _ => ("y", "z"),
+    };
+    let properties = form.properties(&[a, b, "k", "material", "flipped"])?;
+    let a = properties.require(a)?.numbers(2)?;
+    let b = properties.require(b)?.numbers(2)?;
+    let k = properties.number("k")?;
+    let material = parse_material(&properties.require("material")?.nested()?, base)?;
+    let flipped = properties.flag("flipped")?;
+    Ok(match form.name {
+        "xy-rect" => {
+            let rect = XYRect::new(a[0], a[1], b[0], b[1], k, material);
+            if flipped {
+                Box::new(rect.flipped())
+            } else {
+                Box::new(rect)
+            }
+        }
+        "xz-rect" => {
+            let rect = XZRect::new(a[0], a[1], b[0], b[1], k, material);
+            if flipped {
+                Box::new(rect.flipped())
+            } else {
+                Box::new(rect)
+            }
+        }
+        _ => {
+            let rect = YZRect::new(a[0], a[1], b[0], b[1], k, material);
+            if flipped {
+                Box::new(rect.flipped())
+            } else {
+                Box::new(rect)
+            }
+        }
+    })
+}
+
+/// Returns whether the surface is a light source of a shape that can be
+/// sampled directly.
+pub fn is_sampled_light(form: &Form) -> bool {
+    match form.name {
+        "transformed" => {
+            property(form, "surface").is_some_and(|surface| is_sampled_light(&surface))
+        }
+        _ => {
+            is_sampled_shape(form)
+                && property(form, "material")
+                    .is_some_and(|material| material.name == "diffuse-light")
+        }
+    }
+}
+
+/// Returns whether the surface has a shape that can be sampled directly.
+fn is_sampled_shape(form: &Form) -> bool {
+    match form.name {
+        "transformed" => {
+            property(form, "surface").is_some_and(|surface| is_sampled_shape(&surface))
+        }
+        name => matches!(
+            name,
+            "sphere" | "moving-sphere" | "triangle" | "xy-rect" | "xz-rect" | "yz-rect"
+        ),
+    }
+}
+
+/// Returns the form nested in the named property of a form, if it has one.
+fn property<'a>(form: &Form<'a>, name: &str) -> Option<Form<'a>> {
+    form.args.iter().find_map(|arg| match Form::new(arg) {
+        Ok(property) if property.name == name => property.nested().ok(),
+        _ => None,
+    })
+}
+
+/// Loads a mesh from an OBJ file. Each material group of the file takes the
+/// material of the same name from `materials`, falling back to `material`.
+fn parse_mesh(form: &Form, base: &Path) -> Result<Box<dyn Surface>, SceneError> {
+    let properties = form.properties(&["file", "material", "materials"])?;
+    let file = properties.require("file")?;
+    let path = base.join(file.string()?);
+    let model =
+        ObjModel::load(&path).map_err(|err| file.error(format!("{}: {}", path.display(), err)))?;
+
+    let overrides = match properties.get("materials") {
+        Some(materials) => materials.properties(model.groups())?,
+        None => Properties::default(),
+    };
+    let fallback = properties.get("material");
+    let mut materials = Vec::new();
+    for group in model.groups() {
+        let material = match (overrides.get(group), fallback) {
+            (Some(form), _) => form.nested()?,
+            (None, Some(form)) => form.nested()?,
+            (None, None) => {
+                let name = if group.is_empty() { "default" } else { group };
+                return Err(form.error(format!(
+                    "no material for the {} group of {}",
+                    name,
+                    path.display()
+                )));
+            }
+        };
+        materials.push(parse_material(&material, base)?);
+    }
+    let mut materials = materials.into_iter();
+    Ok(Box::new(model.into_mesh(|_| materials.next().unwrap())))
+}
@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use crate::scene::form::Form;
+use crate::scene::SceneError;
+use crate::texture::{
+    Addressing, CheckerTexture, ColorSpace, ConstantTexture, Filter, ImageTexture, MarbleTexture,
+    NoiseTexture, Texture, Turbulence, WoodTexture,
+};
+use crate::vector::Vector3;
+
+pub fn parse_texture(form: &Form, base: &Path) -> Result<Box<dyn Texture>, SceneError> {
+    match form.name {
+        "constant" => Ok(Box::new(ConstantTexture::new(form.vector()?))),
+        "checker" => match form.args {
+            [odd, even] => Ok(Box::new(CheckerTexture::new(
+                parse_texture(&Form::new(odd)?, base)?,
+                parse_texture(&Form::new(even)?, base)?,
+            ))),
+            _ => Err(form.error("'checker' expects two textures")),
+        },
+        "image" => parse_image(form, base),
+        "noise" | "marble" | "wood" => parse_noise(form, base),
+        name => Err(form.error(format!("unknown texture '{}'", name))),
+    }
+}
+
+/// Builds one of the textures made from Perlin noise.
+fn parse_noise(form: &Form, base: &Path) -> Result<Box<dyn Texture>, SceneError> {
+    let (low, high) = match form.name {
+        "noise" => ("low", "high"),
+        "marble" => ("vein", "base"),
+        _ => ("early", "late"),
+    };
+    let mut allowed = vec!["seed", "scale", "octaves", "lacunarity", "gain", low, high];
+    if form.name != "noise" {
+        allowed.push("strength");
+    }
+    let properties = form.properties(&allowed)?;
+
+    let seed = properties.integer_or("seed", 0)?;
+    let defaults = Turbulence::default();
+    let octaves = properties.integer_or("octaves", u64::from(defaults.octaves()))?;
+    if octaves == 0 || octaves > 32 {
+        let octaves = properties.get("octaves").unwrap();
+        return Err(octaves.error("'octaves' must be between 1 and 32"));
+    }
+    let turbulence = Turbulence::new(
+        octaves as u32,
+        properties.number_or("lacunarity", defaults.lacunarity())?,
+        properties.number_or("gain", defaults.gain())?,
+    );
+    let scale = properties.number_or("scale", 1.0)?;
+    Ok(match form.name {
+        "noise" => Box::new(
+            NoiseTexture::new(
+                seed,
+                scale,
+                properties.texture_or(low, Vector3::new(0.0, 0.0, 0.0), base)?,
+                properties.texture_or(high, Vector3::new(1.0, 1.0, 1.0), base)?,
+            )
+            .with_turbulence(turbulence),
+        ),
+        "marble" => Box::new(
+            MarbleTexture::new(
+                seed,
+                scale,
+                properties.texture_or(low, Vector3::new(0.2, 0.2, 0.25), base)?,
+                properties.texture_or(high, Vector3::new(0.9, 0.9, 0.88), base)?,
+            )
+            .with_turbulence(turbulence)
+            .with_strength(properties.number_or("strength", 10.0)?),
+        ),
+        _ => Box::new(
+            WoodTexture::new(
+                seed,
+                scale,
+                properties.texture_or(low, Vector3::new(0.75, 0.55, 0.33), base)?,
+                properties.texture_or(high, Vector3::new(0.4, 0.24, 0.12), base)?,
+            )
+            .with_turbulence(turbulence)
+            .with_strength(properties.number_or("strength", 2.0)?),
+        ),
+    })
+}
+
+/// Loads an image texture from a PNG file.
+fn parse_image(form: &Form, base: &Path) -> Result<Box<dyn Texture>, SceneError> {
+    let properties = form.properties(&["file", "color-space", "filter", "addressing"])?;
+    let color_space = match properties.get("color-space") {
+        None => ColorSpace::Srgb,
+        Some(property) => match property.word()? {
+            "srgb" => ColorSpace::Srgb,
+            "linear" => ColorSpace::Linear,
+            name => return Err(property.error(format!("unknown color space '{}'", name))),
+        },
+    };
+    let filter = match properties.get("filter") {
+        None => Filter::Bilinear,
+        Some(property) => match property.word()? {
+            "nearest" => Filter::Nearest,
+            "bilinear" => Filter::Bilinear,
+            name => return Err(property.error(format!("unknown filter '{}'", name))),
+        },
+    };
+    let addressing = match properties.get("addressing") {
+        None => Addressing::Wrap,
+        Some(property) => match property.word()? {
+            "wrap" => Addressing::Wrap,
+            "clamp" => Addressing::Clamp,
+            "mirror" => Addressing::Mirror,
+            name => return Err(property.error(format!("unknown addressing '{}'", name))),
+        },
+    };
+    let file = properties.require("file")?;
+    let path = base.join(file.string()?);
+    let texture = ImageTexture::load(&path, color_space)
+        .map_err(|err| file.error(format!("{}: {}", path.display(), err)))?;
+    Ok(Box::new(
+        texture.with_filter(filter).with_addressing(addressing),
+    ))
+}
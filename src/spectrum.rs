@@ -0,0 +1,200 @@
+//! Conversions between RGB colors and spectra for spectral rendering.
+//!
+//! A spectral render traces every path at a single wavelength in nanometres.
+//! The RGB colors of materials, lights and the background are turned into
+//! values at that wavelength by Smits' upsampling, and the light arriving
+//! along the path is turned back into the RGB color it adds to the pixel
+//! through the CIE 1931 color matching functions.
+
+use crate::math::{next_random, Rng};
+use crate::vector::Vector3;
+
+/// The shortest wavelength traced, in nanometres.
+pub const MIN_WAVELENGTH: f32 = 360.0;
+
+/// The longest wavelength traced, in nanometres.
+pub const MAX_WAVELENGTH: f32 = 830.0;
+
+/// Chooses three wavelengths between the limits, each with a density
+/// following the sensitivity of the eye so that little time is spent on
+/// wavelengths that barely show. This is the distribution used by PBRT, and
+/// the three are spread evenly across it so that together they cover the
+/// spectrum better than independent choices would.
+pub fn sample_wavelengths(rng: &mut Rng) -> Vector3 {
+    let u = next_random(rng);
+    let at = |offset: f32| {
+        let u = (u + offset).fract();
+        538.0 - 138.888_89 * (0.856_910_6 - 1.827_502 * u).atanh()
+    };
+    Vector3::new(at(0.0), at(1.0 / 3.0), at(2.0 / 3.0))
+}
+
+/// Returns the density with which `sample_wavelengths` chooses each of its
+/// wavelengths.
+#[inline]
+pub fn wavelength_pdf(wavelength: f32) -> f32 {
+    if !(MIN_WAVELENGTH..=MAX_WAVELENGTH).contains(&wavelength) {
+        return 0.0;
+    }
+    let cosh = (0.0072 * (wavelength - 538.0)).cosh();
+    0.003_939_804 / (cosh * cosh)
+}
+
+/// Returns the CIE 1931 color matching functions x, y and z at the
+/// wavelength, using the piecewise Gaussian fit of Wyman, Sloan and Shirley.
+pub fn cie_xyz(wavelength: f32) -> Vector3 {
+    let lobe = |mean: f32, below: f32, above: f32| {
+        let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    Vector3::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+/// Returns the linear sRGB color of light of the given value at the
+/// wavelength alone.
+///
+/// Colors are balanced so that a spectrum of constant value 1 integrates to
+/// white, which makes the spectra that `rgb_to_spectrum` gives for gray
+/// colors come back as the same gray.
+pub fn spectrum_to_rgb(value: f32, wavelength: f32) -> Vector3 {
+    // The rows of the XYZ to linear sRGB matrix, each divided by the channel
+    // that a constant spectrum of 1 integrates to over the traced range.
+    const RED: [f32; 3] = [
+        3.240_454_2 / 128.335_16,
+        -1.537_138_5 / 128.335_16,
+        -0.498_531_4 / 128.335_16,
+    ];
+    const GREEN: [f32; 3] = [
+        -0.969_266 / 101.543_79,
+        1.876_010_8 / 101.543_79,
+        0.041_556 / 101.543_79,
+    ];
+    const BLUE: [f32; 3] = [
+        0.055_643_4 / 97.116_89,
+        -0.204_025_9 / 97.116_89,
+        1.057_225_2 / 97.116_89,
+    ];
+    let xyz = cie_xyz(wavelength) * value;
+    let row = |m: [f32; 3]| m[0] * xyz.x() + m[1] * xyz.y() + m[2] * xyz.z();
+    Vector3::new(row(RED), row(GREEN), row(BLUE))
+}
+
+/// The spectra Smits combines to make up any color, sampled at ten evenly
+/// spaced wavelengths from 380 to 720 nanometres.
+const SMITS_WHITE: [f32; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const SMITS_CYAN: [f32; 10] = [
+    0.971, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0,
+];
+const SMITS_MAGENTA: [f32; 10] = [
+    1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959,
+];
+const SMITS_YELLOW: [f32; 10] = [
+    0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.984,
+];
+const SMITS_RED: [f32; 10] = [
+    0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f32; 10] = [
+    0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025,
+];
+const SMITS_BLUE: [f32; 10] = [
+    1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Returns the value at the wavelength of a smooth spectrum with the given
+/// linear RGB color, following Smits' "An RGB to Spectrum Conversion for
+/// Reflectances". The spectrum of a gray is flat, and those of other colors
+/// add the spectra of the secondary and primary colors that make up the
+/// difference from their smallest channel.
+pub fn rgb_to_spectrum(color: Vector3, wavelength: f32) -> f32 {
+    let x = ((wavelength - 380.0) / (720.0 - 380.0) * 9.0).clamp(0.0, 9.0);
+    let index = (x as usize).min(8);
+    let fraction = x - index as f32;
+    let at = |basis: &[f32; 10]| basis[index] * (1.0 - fraction) + basis[index + 1] * fraction;
+
+    let (r, g, b) = (color.x(), color.y(), color.z());
+    if r <= g && r <= b {
+        r * at(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * at(&SMITS_CYAN) + (b - g) * at(&SMITS_BLUE)
+            } else {
+                (b - r) * at(&SMITS_CYAN) + (g - b) * at(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * at(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * at(&SMITS_MAGENTA) + (b - r) * at(&SMITS_BLUE)
+            } else {
+                (b - g) * at(&SMITS_MAGENTA) + (r - b) * at(&SMITS_RED)
+            }
+    } else {
+        b * at(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * at(&SMITS_YELLOW) + (g - r) * at(&SMITS_GREEN)
+            } else {
+                (g - b) * at(&SMITS_YELLOW) + (r - g) * at(&SMITS_RED)
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::seeded_rng;
+
+    /// Returns the RGB color of the spectrum estimated from wavelengths
+    /// drawn as a spectral render draws them.
+    fn to_rgb(spectrum: impl Fn(f32) -> f32) -> Vector3 {
+        let mut rng = seeded_rng(2);
+        let n = 100_000;
+        let mut sum = Vector3::default();
+        for _ in 0..n {
+            let wavelengths = sample_wavelengths(&mut rng);
+            for i in 0..3 {
+                let wavelength = wavelengths[i];
+                assert!((MIN_WAVELENGTH..=MAX_WAVELENGTH).contains(&wavelength));
+                sum = sum
+                    + spectrum_to_rgb(spectrum(wavelength), wavelength)
+                        / wavelength_pdf(wavelength);
+            }
+        }
+        sum / (3 * n) as f32
+    }
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        let white = to_rgb(|_| 1.0);
+        assert!(
+            (white - Vector3::new(1.0, 1.0, 1.0)).length() < 0.01,
+            "{:?}",
+            white
+        );
+        let gray = Vector3::new(0.5, 0.5, 0.5);
+        assert!((rgb_to_spectrum(gray, 550.0) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn colors_survive_upsampling() {
+        for &color in &[
+            Vector3::new(0.8, 0.8, 0.8),
+            Vector3::new(0.7, 0.2, 0.2),
+            Vector3::new(0.2, 0.3, 0.1),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ] {
+            let round_trip = to_rgb(|wavelength| rgb_to_spectrum(color, wavelength));
+            assert!(
+                (round_trip - color).length() < 0.12,
+                "{:?} {:?}",
+                color,
+                round_trip
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::surface::rect::AxisRect;
+use crate::surface::Surface;
+use crate::vector::Vector3;
+
+/// An axis-aligned box made of six rectangles sharing one material, with
+/// normals pointing out of the box.
+#[derive(Debug)]
+pub struct BoxSurface {
+    min: Vector3,
+    max: Vector3,
+    faces: [AxisRect; 6],
+    material: Box<dyn Material>,
+}
+
+impl BoxSurface {
+    /// Creates the box spanning between two opposite corners.
+    pub fn new(
+        corner0: Vector3,
+        corner1: Vector3,
+        material: impl Material + 'static,
+    ) -> BoxSurface {
+        let min = corner0.min(corner1);
+        let max = corner0.max(corner1);
+        let faces = [
+            AxisRect::new(0, min.y(), max.y(), min.z(), max.z(), min.x()).flipped(),
+            AxisRect::new(0, min.y(), max.y(), min.z(), max.z(), max.x()),
+            AxisRect::new(1, min.x(), max.x(), min.z(), max.z(), min.y()).flipped(),
+            AxisRect::new(1, min.x(), max.x(), min.z(), max.z(), max.y()),
+            AxisRect::new(2, min.x(), max.x(), min.y(), max.y(), min.z()).flipped(),
+            AxisRect::new(2, min.x(), max.x(), min.y(), max.y(), max.z()),
+        ];
+        BoxSurface {
+            min,
+            max,
+            faces,
+            material: Box::new(material),
+        }
+    }
+
+    #[inline]
+    pub fn min(&self) -> Vector3 {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> Vector3 {
+        self.max
+    }
+}
+
+impl Surface for BoxSurface {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let mut closest = tmax;
+        let mut result = None;
+        for face in &self.faces {
+            if let Some((t, u, v)) = face.intersect(ray, tmin, closest) {
+                closest = t;
+                result = Some((face, t, u, v));
+            }
+        }
+        let (face, t, u, v) = result?;
+        let point = ray.point_at_parameter(t);
+        let (dpdu, dpdv) = face.derivatives();
+        let hit = HitRecord::new(t, point, face.normal(), u, v, self.material.as_ref());
+        Some(hit.with_derivatives(dpdu, dpdv))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn material() -> Lambertian {
+        Lambertian::new(ConstantTexture::new(Vector3::default()))
+    }
+
+    #[test]
+    fn box_normals_point_outwards() {
+        let cube = BoxSurface::new(
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(-1.0, -1.0, -1.0),
+            material(),
+        );
+        let axes = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        for &axis in &axes {
+            for &sign in &[1.0, -1.0] {
+                let outward = sign * axis;
+                let aside = 0.3 * axis.cross(Vector3::new(1.0, 1.0, 1.0));
+                let from_outside = Ray::new(3.0 * outward + aside, -outward, 0.0);
+                let hit = cube.hit(&from_outside, 0.0, 10.0).unwrap();
+                assert_eq!(hit.normal(), outward);
+                assert!((hit.t() - 2.0).abs() < 1e-6);
+
+                let from_inside = Ray::new(Vector3::default(), outward, 0.0);
+                let hit = cube.hit(&from_inside, 0.0, 10.0).unwrap();
+                assert_eq!(hit.normal(), outward);
+                assert_eq!(hit.t(), 1.0);
+            }
+        }
+    }
+}
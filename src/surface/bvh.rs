@@ -0,0 +1,387 @@
+use std::cmp::Ordering;
+
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::ray::Ray;
+use crate::surface::Surface;
+use crate::vector::Vector3;
+
+/// Number of buckets used to approximate the surface area heuristic.
+const SAH_BUCKETS: usize = 12;
+
+/// Largest number of surfaces that may be grouped into a single leaf.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Cost of visiting an interior node relative to testing one surface.
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// Depth of the traversal stack, comfortably deeper than any tree built from
+/// an in-memory scene.
+const STACK_SIZE: usize = 64;
+
+/// A bounding volume hierarchy that accelerates hit tests against a list of
+/// surfaces. The tree is built once using a binned surface area heuristic and
+/// stored as a flat array of nodes in depth-first order.
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    surfaces: Vec<Box<dyn Surface>>,
+    unbounded: Vec<Box<dyn Surface>>,
+}
+
+/// A node of the hierarchy. A leaf refers to `count` consecutive surfaces
+/// starting at `offset`. An interior node has a count of zero, its first child
+/// immediately follows it and its second child is found at `offset`.
+#[derive(Debug)]
+struct Node {
+    bounds: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+#[derive(Debug)]
+struct Primitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector3,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    count: usize,
+    bounds: Option<Aabb>,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over the surfaces using their bounding boxes over
+    /// the shutter interval from time0 to time1. Surfaces that cannot be
+    /// bounded are kept aside and tested against every ray.
+    pub fn new(surfaces: Vec<Box<dyn Surface>>, time0: f32, time1: f32) -> Bvh {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut primitives = Vec::new();
+        for surface in surfaces {
+            match surface.bounding_box(time0, time1) {
+                Some(bounds) => {
+                    primitives.push(Primitive {
+                        index: bounded.len(),
+                        bounds,
+                        centroid: bounds.centroid(),
+                    });
+                    bounded.push(Some(surface));
+                }
+                None => unbounded.push(surface),
+            }
+        }
+
+        let mut nodes = Vec::new();
+        if !primitives.is_empty() {
+            build(&mut nodes, &mut primitives, 0);
+        }
+
+        // Reorder the surfaces so that every leaf refers to a contiguous range.
+        let surfaces = primitives
+            .iter()
+            .map(|primitive| bounded[primitive.index].take().unwrap())
+            .collect();
+
+        Bvh {
+            nodes,
+            surfaces,
+            unbounded,
+        }
+    }
+}
+
+/// Recursively builds the subtree for the primitives, whose first element is
+/// stored at `offset` in the final surface order, and returns the index of its
+/// root node.
+fn build(nodes: &mut Vec<Node>, primitives: &mut [Primitive], offset: usize) -> usize {
+    let bounds = primitives[1..]
+        .iter()
+        .fold(primitives[0].bounds, |acc, p| acc.union(&p.bounds));
+    let index = nodes.len();
+    nodes.push(Node {
+        bounds,
+        offset,
+        count: primitives.len(),
+        axis: 0,
+    });
+    if primitives.len() == 1 {
+        return index;
+    }
+
+    let centroid_bounds = primitives[1..].iter().fold(
+        Aabb::new(primitives[0].centroid, primitives[0].centroid),
+        |acc, p| acc.union(&Aabb::new(p.centroid, p.centroid)),
+    );
+    let axis = centroid_bounds.longest_axis();
+    let lo = centroid_bounds.min()[axis];
+    let hi = centroid_bounds.max()[axis];
+    if hi <= lo {
+        // All centroids coincide so no split can separate the primitives.
+        if primitives.len() <= MAX_LEAF_SIZE {
+            return index;
+        }
+        let mid = primitives.len() / 2;
+        return split(nodes, primitives, offset, index, axis, mid);
+    }
+
+    let bucket_of = |p: &Primitive| {
+        let b = (SAH_BUCKETS as f32 * (p.centroid[axis] - lo) / (hi - lo)) as usize;
+        b.min(SAH_BUCKETS - 1)
+    };
+    let mut buckets = [Bucket {
+        count: 0,
+        bounds: None,
+    }; SAH_BUCKETS];
+    for p in primitives.iter() {
+        let bucket = &mut buckets[bucket_of(p)];
+        bucket.count += 1;
+        bucket.bounds = Some(match bucket.bounds {
+            Some(b) => b.union(&p.bounds),
+            None => p.bounds,
+        });
+    }
+
+    // Estimate the cost of splitting after each bucket and keep the cheapest.
+    let area = bounds.surface_area();
+    let mut best_cost = f32::MAX;
+    let mut best_split = 0;
+    for split in 1..SAH_BUCKETS {
+        let (left, right) = buckets.split_at(split);
+        let (count0, area0) = sum_buckets(left);
+        let (count1, area1) = sum_buckets(right);
+        if count0 == 0 || count1 == 0 {
+            continue;
+        }
+        let cost = if area > 0.0 {
+            TRAVERSAL_COST + (count0 as f32 * area0 + count1 as f32 * area1) / area
+        } else {
+            TRAVERSAL_COST + primitives.len() as f32
+        };
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = split;
+        }
+    }
+
+    if primitives.len() <= MAX_LEAF_SIZE && best_cost >= primitives.len() as f32 {
+        return index;
+    }
+
+    let mut mid = partition(primitives, |p| bucket_of(p) < best_split);
+    if mid == 0 || mid == primitives.len() {
+        mid = median(primitives, axis);
+    }
+    split(nodes, primitives, offset, index, axis, mid)
+}
+
+/// Turns the node at `index` into an interior node whose children hold the
+/// primitives before and after `mid`.
+fn split(
+    nodes: &mut Vec<Node>,
+    primitives: &mut [Primitive],
+    offset: usize,
+    index: usize,
+    axis: usize,
+    mid: usize,
+) -> usize {
+    let (left, right) = primitives.split_at_mut(mid);
+    build(nodes, left, offset);
+    let second = build(nodes, right, offset + mid);
+    let node = &mut nodes[index];
+    node.offset = second;
+    node.count = 0;
+    node.axis = axis;
+    index
+}
+
+/// Reorders the primitives so that the first half lies below the median
+/// centroid along the axis and returns the index of the median.
+fn median(primitives: &mut [Primitive], axis: usize) -> usize {
+    let mid = primitives.len() / 2;
+    primitives.select_nth_unstable_by(mid, |a, b| {
+        a.centroid[axis]
+            .partial_cmp(&b.centroid[axis])
+            .unwrap_or(Ordering::Equal)
+    });
+    mid
+}
+
+fn sum_buckets(buckets: &[Bucket]) -> (usize, f32) {
+    let mut count = 0;
+    let mut bounds: Option<Aabb> = None;
+    for bucket in buckets {
+        count += bucket.count;
+        if let Some(b) = bucket.bounds {
+            bounds = Some(bounds.map_or(b, |acc| acc.union(&b)));
+        }
+    }
+    (count, bounds.map_or(0.0, |b| b.surface_area()))
+}
+
+/// Reorders the primitives so that those matching the predicate come first and
+/// returns the number of matching primitives.
+fn partition<F>(primitives: &mut [Primitive], predicate: F) -> usize
+where
+    F: Fn(&Primitive) -> bool,
+{
+    let mut first = 0;
+    for i in 0..primitives.len() {
+        if predicate(&primitives[i]) {
+            primitives.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+impl Surface for Bvh {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let mut closest = tmax;
+        let mut result: Option<HitRecord> = None;
+        for surface in &self.unbounded {
+            if let Some(hit) = surface.hit(ray, tmin, closest) {
+                closest = hit.t();
+                result = Some(hit);
+            }
+        }
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let inv_direction = Vector3::new(
+            1.0 / direction.x(),
+            1.0 / direction.y(),
+            1.0 / direction.z(),
+        );
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut depth = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node
+                .bounds
+                .hit_inverse(origin, inv_direction, tmin, closest)
+            {
+                if node.count > 0 {
+                    for surface in &self.surfaces[node.offset..node.offset + node.count] {
+                        if let Some(hit) = surface.hit(ray, tmin, closest) {
+                            closest = hit.t();
+                            result = Some(hit);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first so that
+                    // the far child is more likely to be culled.
+                    let (near, far) = if inv_direction[node.axis] < 0.0 {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[depth] = far;
+                    depth += 1;
+                    current = near;
+                    continue;
+                }
+            }
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+            current = stack[depth];
+        }
+        result
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(|root| root.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::surface::{MovingSphere, Sphere};
+    use crate::texture::ConstantTexture;
+
+    fn random_vector(rng: &mut StdRng, scale: f32) -> Vector3 {
+        Vector3::new(
+            scale * (2.0 * rng.gen::<f32>() - 1.0),
+            scale * (2.0 * rng.gen::<f32>() - 1.0),
+            scale * (2.0 * rng.gen::<f32>() - 1.0),
+        )
+    }
+
+    fn random_scene(seed: u64, count: usize) -> Vec<Box<dyn Surface>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut surfaces: Vec<Box<dyn Surface>> = Vec::new();
+        for _ in 0..count {
+            let material = Lambertian::new(ConstantTexture::new(Vector3::new(0.5, 0.5, 0.5)));
+            let center = random_vector(&mut rng, 10.0);
+            let radius = 0.05 + 0.5 * rng.gen::<f32>();
+            if rng.gen::<f32>() < 0.5 {
+                surfaces.push(Box::new(Sphere::new(center, radius, material)));
+            } else {
+                let center1 = center + random_vector(&mut rng, 0.5);
+                surfaces.push(Box::new(MovingSphere::new(
+                    center, center1, 0.0, 1.0, radius, material,
+                )));
+            }
+        }
+        surfaces
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let linear = random_scene(7, 500);
+        let bvh = Bvh::new(random_scene(7, 500), 0.0, 1.0);
+        // Every sphere is bounded, so all of them sit in a tree that
+        // splits them up rather than in the list tested against every ray.
+        assert!(bvh.unbounded.is_empty());
+        assert_eq!(bvh.surfaces.len(), 500);
+        assert!(bvh.nodes.iter().filter(|node| node.count == 0).count() > 100);
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut hits = 0;
+        for _ in 0..20_000 {
+            let origin = random_vector(&mut rng, 15.0);
+            let target = random_vector(&mut rng, 10.0);
+            let ray = Ray::new(origin, target - origin, rng.gen::<f32>());
+            let expected = linear.hit(&ray, 0.001, f32::MAX);
+            let actual = bvh.hit(&ray, 0.001, f32::MAX);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(e), Some(a)) => {
+                    hits += 1;
+                    assert_eq!(e.t(), a.t());
+                    assert_eq!(e.point(), a.point());
+                    assert_eq!(e.normal(), a.normal());
+                }
+                (e, a) => panic!("linear scan found {:?} but bvh found {:?}", e, a),
+            }
+        }
+        assert!(hits > 1000);
+    }
+
+    #[test]
+    fn bounds_enclose_surfaces() {
+        let surfaces = random_scene(3, 100);
+        let expected = surfaces.bounding_box(0.0, 1.0);
+        assert!(expected.is_some());
+        let bvh = Bvh::new(surfaces, 0.0, 1.0);
+        assert_eq!(bvh.bounding_box(0.0, 1.0), expected);
+    }
+}
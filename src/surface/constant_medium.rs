@@ -0,0 +1,214 @@
+use std::f32;
+
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::{Isotropic, Material};
+use crate::math::hashed_random;
+use crate::ray::Ray;
+use crate::surface::Surface;
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Distance beyond a boundary hit at which the next hit is searched for, so
+/// that the same crossing is not found twice, relative to the ray parameter
+/// of the hit once that exceeds one.
+const CROSSING_OFFSET: f32 = 1e-4;
+
+/// Returns a ray parameter just past `t`. The offset grows with `t`, as a
+/// fixed one is lost to rounding far from the origin of the ray, where the
+/// search would then find the same crossing forever.
+#[inline]
+fn past(t: f32) -> f32 {
+    t + t.abs().max(1.0) * CROSSING_OFFSET
+}
+
+/// A volume of uniform density filling the inside of a closed boundary
+/// surface, such as fog or smoke. A ray passing through it is scattered at a
+/// distance drawn from the exponential distribution of free flights, and
+/// otherwise leaves it unchanged.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Box<dyn Surface>,
+    density: f32,
+    phase: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// Fills the boundary with a medium whose chance of scattering a ray per
+    /// unit of distance is `density`, and which scatters light of the color
+    /// given by the albedo.
+    pub fn new(boundary: impl Surface, density: f32, albedo: impl Texture) -> ConstantMedium {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            phase: Box::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Surface for ConstantMedium {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        // The distance is a hash of the ray rather than a draw from the
+        // path's generator, which `Surface::hit` has no access to. Hashing
+        // also keeps the answer the same whenever the ray is asked about
+        // again, as a hierarchy does with a shorter `tmax` once it has found
+        // a closer surface, so the medium cannot both scatter a ray and let
+        // it through.
+        let (origin, direction) = (ray.origin(), ray.direction());
+        let random = hashed_random(&[
+            origin.x(),
+            origin.y(),
+            origin.z(),
+            direction.x(),
+            direction.y(),
+            direction.z(),
+            ray.time(),
+        ]);
+        let mut remaining = -(1.0 - random).ln() / self.density;
+        let length = direction.length();
+
+        // Walk the spans of the ray inside the boundary, starting from any
+        // span already containing the origin. As free flights have no
+        // memory, the distance left over from one span carries into the next.
+        let mut search = -f32::MAX;
+        while search < tmax {
+            let enter = self.boundary.hit(ray, search, f32::MAX)?.t();
+            let exit = self.boundary.hit(ray, past(enter), f32::MAX)?.t();
+            let start = enter.max(tmin);
+            let end = exit.min(tmax);
+            if start < end {
+                let inside = (end - start) * length;
+                if remaining < inside {
+                    let t = start + remaining / length;
+                    // The normal is arbitrary, as the phase function
+                    // scatters the same way whatever it is.
+                    let normal = Vector3::new(1.0, 0.0, 0.0);
+                    let point = ray.point_at_parameter(t);
+                    return Some(HitRecord::new(
+                        t,
+                        point,
+                        normal,
+                        0.0,
+                        0.0,
+                        self.phase.as_ref(),
+                    ));
+                }
+                remaining -= inside;
+            }
+            search = past(exit);
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::surface::Sphere;
+    use crate::texture::ConstantTexture;
+
+    fn fog(density: f32) -> ConstantMedium {
+        let white = || ConstantTexture::new(Vector3::new(1.0, 1.0, 1.0));
+        let boundary = Sphere::new(Vector3::default(), 1.0, Lambertian::new(white()));
+        ConstantMedium::new(boundary, density, white())
+    }
+
+    /// Returns the fraction of rays leaving points just inside the sphere's
+    /// surface, offset sideways by tiny amounts, that cross the medium.
+    fn transmitted(medium: &ConstantMedium, origin_x: f32, tmax: f32) -> f32 {
+        let n = 20_000;
+        let passed = (0..n)
+            .filter(|&i| {
+                let offset = i as f32 * 1e-6;
+                let ray = Ray::new(
+                    Vector3::new(origin_x, offset, 0.0),
+                    Vector3::new(1.0, 0.0, 0.0),
+                    0.0,
+                );
+                medium.hit(&ray, 0.0, tmax).is_none()
+            })
+            .count();
+        passed as f32 / n as f32
+    }
+
+    #[test]
+    fn transmittance_follows_beer_lambert() {
+        let medium = fog(0.5);
+        // From outside, through the full diameter.
+        let expected = (-0.5f32 * 2.0).exp();
+        assert!((transmitted(&medium, -3.0, f32::MAX) - expected).abs() < 0.015);
+        // From the center, through the radius only.
+        let expected = (-0.5f32).exp();
+        assert!((transmitted(&medium, 0.0, f32::MAX) - expected).abs() < 0.015);
+        // Stopping halfway to the far side.
+        let expected = (-0.5f32 * 0.5).exp();
+        assert!((transmitted(&medium, 0.0, 0.5) - expected).abs() < 0.015);
+    }
+
+    #[test]
+    fn hits_lie_inside_the_boundary() {
+        let medium = fog(4.0);
+        for i in 0..1000 {
+            let ray = Ray::new(
+                Vector3::new(-3.0, i as f32 * 1e-3 - 0.5, 0.2),
+                Vector3::new(1.0, 0.0, 0.0),
+                0.0,
+            );
+            if let Some(hit) = medium.hit(&ray, 0.0, f32::MAX) {
+                assert!(hit.point().length() < 1.0);
+                assert!(hit.t() >= 0.0);
+            }
+        }
+        let outside = Ray::new(
+            Vector3::new(-3.0, 2.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(medium.hit(&outside, 0.0, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn crosses_media_far_from_the_ray_origin() {
+        // Far enough away that a fixed offset is lost to rounding. The walk
+        // runs on another thread so that a regression fails instead of
+        // hanging.
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let white = || ConstantTexture::new(Vector3::new(1.0, 1.0, 1.0));
+            let boundary = Sphere::new(
+                Vector3::new(0.0, 0.0, -3000.0),
+                100.0,
+                Lambertian::new(white()),
+            );
+            let medium = ConstantMedium::new(boundary, 0.01, white());
+            let passed = (0..1000)
+                .filter(|&i| {
+                    let ray = Ray::new(
+                        Vector3::new(i as f32 * 1e-3, 0.0, 0.0),
+                        Vector3::new(0.0, 0.0, -1.0),
+                        0.0,
+                    );
+                    match medium.hit(&ray, 0.0, f32::MAX) {
+                        Some(hit) => {
+                            let center = Vector3::new(0.0, 0.0, -3000.0);
+                            assert!((hit.point() - center).length() < 100.1);
+                            false
+                        }
+                        None => true,
+                    }
+                })
+                .count();
+            sender.send(passed as f32 / 1000.0).unwrap();
+        });
+        let transmitted = receiver
+            .recv_timeout(std::time::Duration::from_secs(30))
+            .expect("the walk through the medium did not finish");
+        let expected = (-0.01f32 * 200.0).exp();
+        assert!((transmitted - expected).abs() < 0.05);
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::Rng;
+use crate::ray::Ray;
+use crate::surface::transformed::Placement;
+use crate::surface::Surface;
+use crate::vector::{Matrix4, Vector3};
+
+/// A copy of shared geometry placed in the world by its own transform.
+/// Instances hold the geometry and any material override by reference, so
+/// each one costs only its transform however large the geometry is.
+#[derive(Debug)]
+pub struct Instance {
+    geometry: Arc<dyn Surface>,
+    placement: Placement,
+    material: Option<Arc<dyn Material>>,
+}
+
+impl Instance {
+    /// Places the geometry by the transform from its object space to world
+    /// space. Hits keep the materials of the geometry unless overridden.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transform cannot be inverted.
+    pub fn new(geometry: Arc<dyn Surface>, transform: Matrix4) -> Instance {
+        Instance {
+            geometry,
+            placement: Placement::new(transform).expect("instance transform must be invertible"),
+            material: None,
+        }
+    }
+
+    /// Gives every hit on this instance the material instead of the one the
+    /// geometry has there.
+    pub fn with_material(self, material: Arc<dyn Material>) -> Instance {
+        Instance {
+            material: Some(material),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn geometry(&self) -> &Arc<dyn Surface> {
+        &self.geometry
+    }
+
+    #[inline]
+    pub fn transform(&self) -> &Matrix4 {
+        self.placement.transform()
+    }
+}
+
+impl Surface for Instance {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let material = self.material.as_deref();
+        self.placement
+            .hit(self.geometry.as_ref(), material, ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let bounds = self.geometry.bounding_box(time0, time1)?;
+        Some(self.placement.bounds(&bounds))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.placement.pdf_value(self.geometry.as_ref(), ray)
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        self.placement
+            .random(self.geometry.as_ref(), origin, time, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::surface::Sphere;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn instances_share_geometry() {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let geometry: Arc<dyn Surface> = Arc::new(Sphere::new(
+            Vector3::default(),
+            1.0,
+            Lambertian::new(ConstantTexture::new(white)),
+        ));
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(ConstantTexture::new(white)));
+        let instances: Vec<Instance> = (0..3)
+            .map(|i| {
+                let offset = Vector3::new(3.0 * i as f32, 0.0, -5.0);
+                Instance::new(geometry.clone(), Matrix4::translation(offset))
+            })
+            .collect();
+        let lit = Instance::new(geometry.clone(), Matrix4::identity()).with_material(light);
+        assert_eq!(Arc::strong_count(&geometry), 5);
+
+        let ray = Ray::new(
+            Vector3::new(3.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(instances[0].hit(&ray, 0.001, f32::MAX).is_none());
+        let hit = instances[1].hit(&ray, 0.001, f32::MAX).unwrap();
+        assert_eq!(hit.t(), 4.0);
+        assert_eq!(hit.normal(), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            hit.material().emitted(0.0, 0.0, hit.point()),
+            Vector3::default()
+        );
+
+        let ray = Ray::new(
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let hit = lit.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert_eq!(hit.material().emitted(0.0, 0.0, hit.point()), white);
+    }
+}
@@ -1,20 +1,95 @@
 use std::fmt::Debug;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::hit_record::HitRecord;
+use crate::math::{next_random, random_unit_vector, Rng};
 use crate::ray::Ray;
+use crate::vector::Vector3;
 
+mod box_surface;
+mod bvh;
+mod constant_medium;
+mod instance;
 mod moving_sphere;
+mod rect;
 mod sphere;
+mod transformed;
+mod triangle;
+mod triangle_mesh;
 
+pub use self::box_surface::BoxSurface;
+pub use self::bvh::Bvh;
+pub use self::constant_medium::ConstantMedium;
+pub use self::instance::Instance;
 pub use self::moving_sphere::MovingSphere;
+pub use self::rect::{XYRect, XZRect, YZRect};
 pub use self::sphere::Sphere;
+pub use self::transformed::Transformed;
+pub use self::triangle::Triangle;
+pub use self::triangle_mesh::{MeshFace, TriangleMesh};
 
 pub trait Surface: Debug + Sync + Send + 'static {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>>;
+
+    /// Returns a box enclosing the surface over the shutter interval from
+    /// time0 to time1, or None if the surface is unbounded.
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb>;
+
+    /// Returns the solid angle density with which `random` chooses the
+    /// direction of the ray from its origin. Surfaces that cannot be sampled
+    /// as lights return zero.
+    fn pdf_value(&self, _ray: &Ray) -> f32 {
+        0.0
+    }
+
+    /// Returns a random direction from the origin towards the surface as it
+    /// is at the given time, drawn with the density given by `pdf_value`.
+    fn random(&self, _origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        random_unit_vector(rng)
+    }
 }
 
-impl Surface for Vec<Box<Surface>> {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+impl Surface for Box<dyn Surface> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.as_ref().hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.as_ref().bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.as_ref().pdf_value(ray)
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        self.as_ref().random(origin, time, rng)
+    }
+}
+
+/// Lets a surface be shared, such as a light that is both part of the world
+/// and sampled directly.
+impl Surface for Arc<dyn Surface> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.as_ref().hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.as_ref().bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.as_ref().pdf_value(ray)
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        self.as_ref().random(origin, time, rng)
+    }
+}
+
+impl Surface for Vec<Box<dyn Surface>> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
         let mut closest = tmax;
         let mut result: Option<HitRecord> = None;
         for surface in self {
@@ -25,4 +100,131 @@ impl Surface for Vec<Box<Surface>> {
         }
         result
     }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let mut result: Option<Aabb> = None;
+        for surface in self {
+            let bounds = surface.bounding_box(time0, time1)?;
+            result = Some(match result {
+                Some(acc) => acc.union(&bounds),
+                None => bounds,
+            });
+        }
+        result
+    }
+
+    /// Returns the density of choosing the direction when each surface is
+    /// equally likely to be sampled.
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self.iter().map(|surface| surface.pdf_value(ray)).sum();
+        sum / self.len() as f32
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        if self.is_empty() {
+            return random_unit_vector(rng);
+        }
+        let index = ((next_random(rng) * self.len() as f32) as usize).min(self.len() - 1);
+        self[index].random(origin, time, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::math::seeded_rng;
+    use crate::texture::ConstantTexture;
+    use crate::vector::Matrix4;
+    use std::f32::consts::PI;
+
+    fn lights() -> Vec<Box<dyn Surface>> {
+        let material = || Lambertian::new(ConstantTexture::new(Vector3::new(1.0, 1.0, 1.0)));
+        vec![
+            Box::new(Sphere::new(Vector3::new(0.0, 3.0, 0.0), 0.8, material())),
+            Box::new(MovingSphere::new(
+                Vector3::new(-2.0, 0.0, 1.0),
+                Vector3::new(-2.0, 1.0, 1.0),
+                0.0,
+                1.0,
+                0.5,
+                material(),
+            )),
+            Box::new(Triangle::new(
+                Vector3::new(1.0, -1.0, -1.0),
+                Vector3::new(3.0, -1.0, -1.0),
+                Vector3::new(1.0, 2.0, -2.0),
+                material(),
+            )),
+            Box::new(XZRect::new(-1.0, 1.0, 0.0, 2.0, 2.0, material()).flipped()),
+            Box::new(YZRect::new(-1.0, 1.0, 0.0, 1.5, 1.5, material())),
+            Box::new(
+                Transformed::new(
+                    Sphere::new(Vector3::default(), 0.5, material()),
+                    Matrix4::scaling(Vector3::new(2.0, 0.5, 1.0)),
+                )
+                .rotated(Vector3::new(1.0, 1.0, 0.0), 40.0)
+                .translated(Vector3::new(0.0, -2.0, 0.0)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn sampled_directions_reach_the_surface() {
+        let mut rng = seeded_rng(1);
+        let origin = Vector3::new(0.0, 0.5, 0.5);
+        for light in lights() {
+            for _ in 0..1000 {
+                let direction = light.random(origin, 0.5, &mut rng).normalized();
+                let ray = Ray::new(origin, direction, 0.5);
+                assert!(light.hit(&ray, 0.0, f32::MAX).is_some(), "{:?}", light);
+                assert!(light.pdf_value(&ray) > 0.0, "{:?}", light);
+            }
+        }
+    }
+
+    #[test]
+    fn densities_integrate_to_one() {
+        let mut rng = seeded_rng(2);
+        let origin = Vector3::new(0.0, 0.5, 0.5);
+        let n = 200_000;
+        for light in lights() {
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let ray = Ray::new(origin, random_unit_vector(&mut rng), 0.5);
+                sum += light.pdf_value(&ray) as f64;
+            }
+            let integral = sum / n as f64 * 4.0 * PI as f64;
+            assert!((integral - 1.0).abs() < 0.05, "{:?}: {}", light, integral);
+        }
+    }
+
+    #[test]
+    fn bounds_cover_shutter_interval() {
+        let material = || Lambertian::new(ConstantTexture::new(Vector3::new(1.0, 1.0, 1.0)));
+        let sphere = Sphere::new(Vector3::new(1.0, 2.0, 3.0), 0.5, material());
+        let expected = Aabb::new(Vector3::new(0.5, 1.5, 2.5), Vector3::new(1.5, 2.5, 3.5));
+        assert_eq!(sphere.bounding_box(0.0, 1.0), Some(expected));
+
+        // Half way through its motion, the sphere only covers half the path.
+        let moving = MovingSphere::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            0.0,
+            1.0,
+            1.0,
+            material(),
+        );
+        let expected = Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(3.0, 1.0, 1.0));
+        assert_eq!(moving.bounding_box(0.0, 0.5), Some(expected));
+
+        let surfaces: Vec<Box<dyn Surface>> = vec![Box::new(sphere), Box::new(moving)];
+        let expected = Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(5.0, 2.5, 3.5));
+        assert_eq!(surfaces.bounding_box(0.0, 1.0), Some(expected));
+        let empty: Vec<Box<dyn Surface>> = Vec::new();
+        assert_eq!(empty.bounding_box(0.0, 1.0), None);
+    }
 }
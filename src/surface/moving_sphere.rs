@@ -1,6 +1,9 @@
+use crate::aabb::Aabb;
 use crate::hit_record::HitRecord;
 use crate::material::Material;
+use crate::math::Rng;
 use crate::ray::Ray;
+use crate::surface::sphere::{sample_sphere, sphere_derivatives, sphere_pdf, sphere_uv};
 use crate::surface::Surface;
 use crate::vector::Vector3;
 
@@ -11,7 +14,7 @@ pub struct MovingSphere {
     time0: f32,
     time1: f32,
     radius: f32,
-    material: Box<Material>,
+    material: Box<dyn Material>,
 }
 
 impl MovingSphere {
@@ -24,11 +27,11 @@ impl MovingSphere {
         material: impl Material + 'static,
     ) -> MovingSphere {
         MovingSphere {
-            center0: center0,
-            center1: center1,
-            time0: time0,
-            time1: time1,
-            radius: radius,
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
             material: Box::new(material),
         }
     }
@@ -40,7 +43,7 @@ impl MovingSphere {
 }
 
 impl Surface for MovingSphere {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
         let center = self.center(ray.time());
         let oc = ray.origin() - center;
         let a = ray.direction().squared_norm();
@@ -52,17 +55,43 @@ impl Surface for MovingSphere {
             if t1 >= tmin && t1 < tmax {
                 let point = ray.point_at_parameter(t1);
                 let normal = (point - center).normalized();
-                let hit = HitRecord::new(t1, point, normal, self.material.as_ref());
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, self.radius);
+                let hit = HitRecord::new(t1, point, normal, u, v, self.material.as_ref())
+                    .with_derivatives(dpdu, dpdv);
                 return Some(hit);
             }
             let t2 = (-b + discriminant.sqrt()) / a;
             if t2 >= tmin && t2 < tmax {
                 let point = ray.point_at_parameter(t2);
                 let normal = (point - center).normalized();
-                let hit = HitRecord::new(t2, point, normal, self.material.as_ref());
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, self.radius);
+                let hit = HitRecord::new(t2, point, normal, u, v, self.material.as_ref())
+                    .with_derivatives(dpdu, dpdv);
                 return Some(hit);
             }
         }
         None
     }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        let center0 = self.center(time0);
+        let center1 = self.center(time1);
+        let box0 = Aabb::new(center0 - extent, center0 + extent);
+        let box1 = Aabb::new(center1 - extent, center1 + extent);
+        Some(box0.union(&box1))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        match self.hit(ray, 0.001, f32::MAX) {
+            Some(_) => sphere_pdf(self.center(ray.time()), self.radius, ray.origin()),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        sample_sphere(self.center(time), self.radius, origin, rng)
+    }
 }
@@ -0,0 +1,359 @@
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::{next_random, Rng};
+use crate::ray::Ray;
+use crate::surface::Surface;
+use crate::vector::Vector3;
+
+/// The geometry of a rectangle lying in a plane perpendicular to one of the
+/// coordinate axes, shared by the rectangle surfaces and by boxes.
+#[derive(Clone, Copy, Debug)]
+pub struct AxisRect {
+    /// The axis the rectangle is perpendicular to.
+    axis: usize,
+    a0: f32,
+    a1: f32,
+    b0: f32,
+    b1: f32,
+    k: f32,
+    flipped: bool,
+}
+
+impl AxisRect {
+    /// Creates the rectangle at `k` along the given axis, spanning from a0 to
+    /// a1 along the next axis and from b0 to b1 along the one after, in x, y,
+    /// z order. Its normal points along the positive axis.
+    pub fn new(axis: usize, a0: f32, a1: f32, b0: f32, b1: f32, k: f32) -> AxisRect {
+        AxisRect {
+            axis,
+            a0: a0.min(a1),
+            a1: a0.max(a1),
+            b0: b0.min(b1),
+            b1: b0.max(b1),
+            k,
+            flipped: false,
+        }
+    }
+
+    /// Returns the rectangle with its normal pointing along the negative axis.
+    pub fn flipped(self) -> AxisRect {
+        AxisRect {
+            flipped: !self.flipped,
+            ..self
+        }
+    }
+
+    /// Returns the indices of the axes spanned by the rectangle.
+    #[inline]
+    fn plane_axes(&self) -> (usize, usize) {
+        match self.axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        }
+    }
+
+    #[inline]
+    fn point(&self, a: f32, b: f32) -> Vector3 {
+        let (a_axis, b_axis) = self.plane_axes();
+        let mut coordinates = [0.0; 3];
+        coordinates[self.axis] = self.k;
+        coordinates[a_axis] = a;
+        coordinates[b_axis] = b;
+        Vector3::new(coordinates[0], coordinates[1], coordinates[2])
+    }
+
+    #[inline]
+    pub fn normal(&self) -> Vector3 {
+        let sign = if self.flipped { -1.0 } else { 1.0 };
+        let mut coordinates = [0.0; 3];
+        coordinates[self.axis] = sign;
+        Vector3::new(coordinates[0], coordinates[1], coordinates[2])
+    }
+
+    /// Returns the rates of change of a point on the rectangle with its
+    /// surface coordinates.
+    #[inline]
+    pub fn derivatives(&self) -> (Vector3, Vector3) {
+        let (a_axis, b_axis) = self.plane_axes();
+        let mut dpdu = [0.0; 3];
+        let mut dpdv = [0.0; 3];
+        dpdu[a_axis] = self.a1 - self.a0;
+        dpdv[b_axis] = self.b1 - self.b0;
+        (
+            Vector3::new(dpdu[0], dpdu[1], dpdu[2]),
+            Vector3::new(dpdv[0], dpdv[1], dpdv[2]),
+        )
+    }
+
+    #[inline]
+    pub fn area(&self) -> f32 {
+        (self.a1 - self.a0) * (self.b1 - self.b0)
+    }
+
+    /// Intersects the ray with the rectangle, returning the ray parameter of
+    /// the hit and its surface coordinates, which run from 0 to 1 across the
+    /// rectangle.
+    #[inline]
+    pub fn intersect(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<(f32, f32, f32)> {
+        let (a_axis, b_axis) = self.plane_axes();
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let t = (self.k - origin[self.axis]) / direction[self.axis];
+        // Written so that the NaN from a ray lying in the plane is a miss.
+        if !(t >= tmin && t < tmax) {
+            return None;
+        }
+        let a = origin[a_axis] + t * direction[a_axis];
+        let b = origin[b_axis] + t * direction[b_axis];
+        if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
+            return None;
+        }
+        let u = (a - self.a0) / (self.a1 - self.a0);
+        let v = (b - self.b0) / (self.b1 - self.b0);
+        Some((t, u, v))
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(self.point(self.a0, self.b0), self.point(self.a1, self.b1))
+    }
+
+    /// Returns the solid angle density of `random` for the direction of the
+    /// ray.
+    pub fn pdf_value(&self, ray: &Ray) -> f32 {
+        let (t, _, _) = match self.intersect(ray, 0.001, f32::MAX) {
+            Some(hit) => hit,
+            None => return 0.0,
+        };
+        let direction = ray.direction();
+        let distance_squared = t * t * direction.squared_norm();
+        let cosine = (direction[self.axis] / direction.length()).abs();
+        if cosine * self.area() > 0.0 {
+            distance_squared / (cosine * self.area())
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the direction from the origin to a point chosen uniformly over
+    /// the area of the rectangle.
+    pub fn random(&self, origin: Vector3, rng: &mut Rng) -> Vector3 {
+        let a = self.a0 + next_random(rng) * (self.a1 - self.a0);
+        let b = self.b0 + next_random(rng) * (self.b1 - self.b0);
+        self.point(a, b) - origin
+    }
+}
+
+/// A rectangle in the plane z = k, spanning from x0 to x1 and y0 to y1. Its
+/// surface coordinates follow x and y and its normal points towards +z
+/// unless it is flipped.
+#[derive(Debug)]
+pub struct XYRect {
+    rect: AxisRect,
+    material: Box<dyn Material>,
+}
+
+impl XYRect {
+    pub fn new(
+        x0: f32,
+        x1: f32,
+        y0: f32,
+        y1: f32,
+        k: f32,
+        material: impl Material + 'static,
+    ) -> XYRect {
+        XYRect {
+            rect: AxisRect::new(2, x0, x1, y0, y1, k),
+            material: Box::new(material),
+        }
+    }
+
+    /// Turns the normal to point towards -z.
+    pub fn flipped(self) -> XYRect {
+        XYRect {
+            rect: self.rect.flipped(),
+            ..self
+        }
+    }
+}
+
+/// A rectangle in the plane y = k, spanning from x0 to x1 and z0 to z1. Its
+/// surface coordinates follow x and z and its normal points towards +y
+/// unless it is flipped.
+#[derive(Debug)]
+pub struct XZRect {
+    rect: AxisRect,
+    material: Box<dyn Material>,
+}
+
+impl XZRect {
+    pub fn new(
+        x0: f32,
+        x1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: impl Material + 'static,
+    ) -> XZRect {
+        XZRect {
+            rect: AxisRect::new(1, x0, x1, z0, z1, k),
+            material: Box::new(material),
+        }
+    }
+
+    /// Turns the normal to point towards -y.
+    pub fn flipped(self) -> XZRect {
+        XZRect {
+            rect: self.rect.flipped(),
+            ..self
+        }
+    }
+}
+
+/// A rectangle in the plane x = k, spanning from y0 to y1 and z0 to z1. Its
+/// surface coordinates follow y and z and its normal points towards +x
+/// unless it is flipped.
+#[derive(Debug)]
+pub struct YZRect {
+    rect: AxisRect,
+    material: Box<dyn Material>,
+}
+
+impl YZRect {
+    pub fn new(
+        y0: f32,
+        y1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: impl Material + 'static,
+    ) -> YZRect {
+        YZRect {
+            rect: AxisRect::new(0, y0, y1, z0, z1, k),
+            material: Box::new(material),
+        }
+    }
+
+    /// Turns the normal to point towards -x.
+    pub fn flipped(self) -> YZRect {
+        YZRect {
+            rect: self.rect.flipped(),
+            ..self
+        }
+    }
+}
+
+/// Hits a rectangle with the given material.
+#[inline]
+fn hit_rect<'a>(
+    rect: &AxisRect,
+    material: &'a dyn Material,
+    ray: &Ray,
+    tmin: f32,
+    tmax: f32,
+) -> Option<HitRecord<'a>> {
+    let (t, u, v) = rect.intersect(ray, tmin, tmax)?;
+    let point = ray.point_at_parameter(t);
+    let (dpdu, dpdv) = rect.derivatives();
+    Some(HitRecord::new(t, point, rect.normal(), u, v, material).with_derivatives(dpdu, dpdv))
+}
+
+impl Surface for XYRect {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_rect(&self.rect, self.material.as_ref(), ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.rect.bounds())
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.rect.pdf_value(ray)
+    }
+
+    fn random(&self, origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        self.rect.random(origin, rng)
+    }
+}
+
+impl Surface for XZRect {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_rect(&self.rect, self.material.as_ref(), ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.rect.bounds())
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.rect.pdf_value(ray)
+    }
+
+    fn random(&self, origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        self.rect.random(origin, rng)
+    }
+}
+
+impl Surface for YZRect {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_rect(&self.rect, self.material.as_ref(), ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.rect.bounds())
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.rect.pdf_value(ray)
+    }
+
+    fn random(&self, origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        self.rect.random(origin, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn material() -> Lambertian {
+        Lambertian::new(ConstantTexture::new(Vector3::default()))
+    }
+
+    #[test]
+    fn rects_report_normals_and_coordinates() {
+        let ray = |origin: Vector3, direction: Vector3| Ray::new(origin, direction, 0.0);
+        let xy = XYRect::new(0.0, 2.0, 0.0, 4.0, -1.0, material());
+        let hit = xy
+            .hit(
+                &ray(Vector3::new(0.5, 3.0, 1.0), Vector3::new(0.0, 0.0, -1.0)),
+                0.0,
+                10.0,
+            )
+            .unwrap();
+        assert_eq!(hit.t(), 2.0);
+        assert_eq!((hit.u(), hit.v()), (0.25, 0.75));
+        assert_eq!(hit.normal(), Vector3::new(0.0, 0.0, 1.0));
+
+        let xz = XZRect::new(0.0, 1.0, 0.0, 1.0, 2.0, material()).flipped();
+        let hit = xz
+            .hit(
+                &ray(Vector3::new(0.2, 0.0, 0.6), Vector3::new(0.0, 1.0, 0.0)),
+                0.0,
+                10.0,
+            )
+            .unwrap();
+        assert_eq!((hit.u(), hit.v()), (0.2, 0.6));
+        assert_eq!(hit.normal(), Vector3::new(0.0, -1.0, 0.0));
+
+        let yz = YZRect::new(0.0, 1.0, 0.0, 1.0, 0.0, material());
+        let parallel = ray(Vector3::new(0.0, 0.5, 0.5), Vector3::new(0.0, 1.0, 0.0));
+        assert!(yz.hit(&parallel, 0.0, 10.0).is_none());
+        let outside = ray(Vector3::new(-1.0, 1.5, 0.5), Vector3::new(1.0, 0.0, 0.0));
+        assert!(yz.hit(&outside, 0.0, 10.0).is_none());
+    }
+}
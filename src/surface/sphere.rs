@@ -1,5 +1,9 @@
+use crate::aabb::Aabb;
+use std::f32::consts::PI;
+
 use crate::hit_record::HitRecord;
 use crate::material::Material;
+use crate::math::{next_random, orthonormal_basis, random_unit_vector, Rng};
 use crate::ray::Ray;
 use crate::surface::Surface;
 use crate::vector::Vector3;
@@ -8,21 +12,21 @@ use crate::vector::Vector3;
 pub struct Sphere {
     center: Vector3,
     radius: f32,
-    material: Box<Material>,
+    material: Box<dyn Material>,
 }
 
 impl Sphere {
     pub fn new(center: Vector3, radius: f32, material: impl Material + 'static) -> Sphere {
         Sphere {
-            center: center,
-            radius: radius,
+            center,
+            radius,
             material: Box::new(material),
         }
     }
 }
 
 impl Surface for Sphere {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().squared_norm();
         let b = oc.dot(ray.direction());
@@ -33,17 +37,155 @@ impl Surface for Sphere {
             if t1 >= tmin && t1 < tmax {
                 let point = ray.point_at_parameter(t1);
                 let normal = (point - self.center).normalized();
-                let hit = HitRecord::new(t1, point, normal, self.material.as_ref());
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, self.radius);
+                let hit = HitRecord::new(t1, point, normal, u, v, self.material.as_ref())
+                    .with_derivatives(dpdu, dpdv);
                 return Some(hit);
             }
             let t2 = (-b + discriminant.sqrt()) / a;
             if t2 >= tmin && t2 < tmax {
                 let point = ray.point_at_parameter(t2);
                 let normal = (point - self.center).normalized();
-                let hit = HitRecord::new(t2, point, normal, self.material.as_ref());
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, self.radius);
+                let hit = HitRecord::new(t2, point, normal, u, v, self.material.as_ref())
+                    .with_derivatives(dpdu, dpdv);
                 return Some(hit);
             }
         }
         None
     }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        match self.hit(ray, 0.001, f32::MAX) {
+            Some(_) => sphere_pdf(self.center, self.radius, ray.origin()),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        sample_sphere(self.center, self.radius, origin, rng)
+    }
+}
+
+/// Returns the surface coordinates of a point on a sphere, given by its unit
+/// direction from the center. The coordinate u is the longitude, running
+/// from -x through +z, +x and -z back to -x, and v the latitude from the
+/// south pole at -y to the north pole at +y.
+#[inline]
+pub fn sphere_uv(direction: Vector3) -> (f32, f32) {
+    let theta = (-direction.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-direction.z()).atan2(direction.x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Returns the rates of change with u and v of a point on a sphere of the
+/// radius, given by its unit direction from the center. Both are zero at the
+/// poles, where the longitude is undefined.
+#[inline]
+pub fn sphere_derivatives(direction: Vector3, radius: f32) -> (Vector3, Vector3) {
+    let (x, y, z) = (direction.x(), direction.y(), direction.z());
+    let sine = (x * x + z * z).sqrt();
+    if sine == 0.0 {
+        return (Vector3::default(), Vector3::default());
+    }
+    let dpdu = (2.0 * PI * radius) * Vector3::new(z, 0.0, -x);
+    let dpdv = (PI * radius) * Vector3::new(-y * x / sine, sine, -y * z / sine);
+    (dpdu, dpdv)
+}
+
+/// Returns the solid angle density of `sample_sphere` for a direction from
+/// the origin that hits the sphere.
+#[inline]
+pub fn sphere_pdf(center: Vector3, radius: f32, origin: Vector3) -> f32 {
+    let ratio = radius * radius / (center - origin).squared_norm();
+    if ratio >= 1.0 {
+        return 1.0 / (4.0 * PI);
+    }
+    let cos_theta_max = (1.0 - ratio).sqrt();
+    // 1 - cos_theta_max rewritten to stay accurate for distant spheres.
+    let solid_angle = 2.0 * PI * ratio / (1.0 + cos_theta_max);
+    1.0 / solid_angle
+}
+
+/// Returns a direction from the origin uniformly distributed over the cone
+/// of directions hitting the sphere, or over all directions if the origin is
+/// inside the sphere.
+#[inline]
+pub fn sample_sphere(center: Vector3, radius: f32, origin: Vector3, rng: &mut Rng) -> Vector3 {
+    let to_center = center - origin;
+    let ratio = radius * radius / to_center.squared_norm();
+    if ratio >= 1.0 {
+        return random_unit_vector(rng);
+    }
+    let cos_theta_max = (1.0 - ratio).sqrt();
+    let one_minus_cos = next_random(rng) * ratio / (1.0 + cos_theta_max);
+    let cos_theta = 1.0 - one_minus_cos;
+    let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+    let phi = 2.0 * PI * next_random(rng);
+    let w = to_center.normalized();
+    let (u, v) = orthonormal_basis(w);
+    sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn hits_report_spherical_coordinates() {
+        let center = Vector3::new(1.0, 2.0, 3.0);
+        let sphere = Sphere::new(
+            center,
+            2.0,
+            Lambertian::new(ConstantTexture::new(Vector3::default())),
+        );
+        let uv_from = |direction: Vector3| {
+            let ray = Ray::new(center + 5.0 * direction, -direction, 0.0);
+            let hit = sphere.hit(&ray, 0.0, f32::MAX).unwrap();
+            (hit.u(), hit.v())
+        };
+        let close = |(u, v): (f32, f32), (eu, ev): (f32, f32)| {
+            assert!(
+                (u - eu).abs() < 1e-5 && (v - ev).abs() < 1e-5,
+                "{} {}",
+                u,
+                v
+            );
+        };
+        close(uv_from(Vector3::new(1.0, 0.0, 0.0)), (0.5, 0.5));
+        close(uv_from(Vector3::new(0.0, 0.0, 1.0)), (0.25, 0.5));
+        close(uv_from(Vector3::new(0.0, 0.0, -1.0)), (0.75, 0.5));
+        assert!(uv_from(Vector3::new(0.0, 1.0, 0.0)).1 > 0.9999);
+        assert!(uv_from(Vector3::new(0.0, -1.0, 0.0)).1 < 1e-4);
+    }
+
+    #[test]
+    fn derivatives_follow_coordinates() {
+        let (center, radius) = (Vector3::new(1.0, 2.0, 3.0), 2.0);
+        let step = 1e-3;
+        for &direction in &[
+            Vector3::new(0.3, 0.5, -0.8).normalized(),
+            Vector3::new(-0.6, -0.7, 0.2).normalized(),
+        ] {
+            let (u, v) = sphere_uv(direction);
+            let (dpdu, dpdv) = sphere_derivatives(direction, radius);
+            // Moving the point a small step along either derivative must
+            // change that coordinate alone, by the same step.
+            for &(derivative, du, dv) in &[(dpdu, step, 0.0), (dpdv, 0.0, step)] {
+                let point = center + radius * direction + step * derivative;
+                let (u1, v1) = sphere_uv((point - center).normalized());
+                assert!((u1 - u - du).abs() < 1e-4, "{} {}", u1 - u, du);
+                assert!((v1 - v - dv).abs() < 1e-4, "{} {}", v1 - v, dv);
+            }
+        }
+    }
 }
@@ -0,0 +1,232 @@
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::Rng;
+use crate::ray::Ray;
+use crate::surface::Surface;
+use crate::vector::{Matrix4, Vector3};
+
+/// An invertible affine transform from the object space of a surface to
+/// world space, with the conversions needed to trace the surface through it.
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    transform: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Placement {
+    /// Returns None if the transform cannot be inverted.
+    pub fn new(transform: Matrix4) -> Option<Placement> {
+        let inverse = transform.inverse()?;
+        Some(Placement { transform, inverse })
+    }
+
+    #[inline]
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    /// Returns the placement applying the transform after this one.
+    pub fn then(&self, transform: Matrix4) -> Option<Placement> {
+        Placement::new(transform * self.transform)
+    }
+
+    #[inline]
+    pub fn object_ray(&self, ray: &Ray) -> Ray {
+        // The direction is left unnormalized so that the ray parameter of a
+        // hit is the same in both spaces.
+        Ray::new(
+            self.inverse.transform_point(ray.origin()),
+            self.inverse.transform_vector(ray.direction()),
+            ray.time(),
+        )
+    }
+
+    /// Traces the ray through the transform to the surface, returning the
+    /// hit in world space with the given material.
+    #[inline]
+    pub fn hit<'a>(
+        &self,
+        surface: &'a dyn Surface,
+        material: Option<&'a dyn Material>,
+        ray: &Ray,
+        tmin: f32,
+        tmax: f32,
+    ) -> Option<HitRecord<'a>> {
+        let hit = surface.hit(&self.object_ray(ray), tmin, tmax)?;
+        // Normals are covectors, so they transform by the inverse transpose
+        // to stay perpendicular to the surface under non-uniform scaling.
+        let normal_transform = self.inverse.transpose();
+        let normal = |n| normal_transform.transform_vector(n).normalized();
+        let world = HitRecord::new(
+            hit.t(),
+            self.transform.transform_point(hit.point()),
+            normal(hit.geometric_normal()),
+            hit.u(),
+            hit.v(),
+            material.unwrap_or_else(|| hit.material()),
+        )
+        .with_derivatives(
+            self.transform.transform_vector(hit.dpdu()),
+            self.transform.transform_vector(hit.dpdv()),
+        );
+        if hit.normal() == hit.geometric_normal() {
+            Some(world)
+        } else {
+            Some(world.with_shading_normal(normal(hit.normal()), ray))
+        }
+    }
+
+    /// Returns a box enclosing the given object space box in world space.
+    pub fn bounds(&self, bounds: &Aabb) -> Aabb {
+        let (min, max) = (bounds.min(), bounds.max());
+        let corner = |i: usize| {
+            self.transform.transform_point(Vector3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            ))
+        };
+        let first = corner(0);
+        let (min, max) = (1..8)
+            .map(corner)
+            .fold((first, first), |(min, max), c| (min.min(c), max.max(c)));
+        Aabb::new(min, max)
+    }
+
+    pub fn pdf_value(&self, surface: &dyn Surface, ray: &Ray) -> f32 {
+        let direction = ray.direction().normalized();
+        let object_ray = self.object_ray(&Ray::new(ray.origin(), direction, ray.time()));
+        // The inverse maps the unit direction to one of this length, and
+        // solid angles around it are stretched by det / length^3.
+        let length = object_ray.direction().length();
+        let jacobian = self.inverse.determinant3().abs() / (length * length * length);
+        surface.pdf_value(&object_ray) * jacobian
+    }
+
+    pub fn random(
+        &self,
+        surface: &dyn Surface,
+        origin: Vector3,
+        time: f32,
+        rng: &mut Rng,
+    ) -> Vector3 {
+        let object_origin = self.inverse.transform_point(origin);
+        let direction = surface.random(object_origin, time, rng);
+        self.transform.transform_vector(direction)
+    }
+}
+
+/// A surface placed in the world by an affine transform, so that one shape
+/// described in its own object space can be moved, rotated and scaled.
+#[derive(Debug)]
+pub struct Transformed {
+    surface: Box<dyn Surface>,
+    placement: Placement,
+}
+
+impl Transformed {
+    /// Places the surface by the transform from its object space to world
+    /// space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transform cannot be inverted.
+    pub fn new(surface: impl Surface, transform: Matrix4) -> Transformed {
+        Transformed {
+            surface: Box::new(surface),
+            placement: Placement::new(transform).expect("surface transform must be invertible"),
+        }
+    }
+
+    /// Moves the surface by the offset after the current transform.
+    pub fn translated(self, offset: Vector3) -> Transformed {
+        self.then(Matrix4::translation(offset))
+    }
+
+    /// Rotates the surface by the angle in degrees about the axis through
+    /// the world origin after the current transform.
+    pub fn rotated(self, axis: Vector3, degrees: f32) -> Transformed {
+        self.then(Matrix4::rotation(axis, degrees))
+    }
+
+    /// Scales the surface about the world origin after the current
+    /// transform.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the factors is zero.
+    pub fn scaled(self, factors: Vector3) -> Transformed {
+        self.then(Matrix4::scaling(factors))
+    }
+
+    fn then(self, transform: Matrix4) -> Transformed {
+        Transformed {
+            placement: self
+                .placement
+                .then(transform)
+                .expect("surface transform must be invertible"),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn transform(&self) -> &Matrix4 {
+        self.placement.transform()
+    }
+}
+
+impl Surface for Transformed {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.placement
+            .hit(self.surface.as_ref(), None, ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let bounds = self.surface.bounding_box(time0, time1)?;
+        Some(self.placement.bounds(&bounds))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        self.placement.pdf_value(self.surface.as_ref(), ray)
+    }
+
+    fn random(&self, origin: Vector3, time: f32, rng: &mut Rng) -> Vector3 {
+        self.placement
+            .random(self.surface.as_ref(), origin, time, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::surface::Sphere;
+    use crate::texture::ConstantTexture;
+
+    fn ellipsoid() -> Transformed {
+        let material = Lambertian::new(ConstantTexture::new(Vector3::default()));
+        Transformed::new(
+            Sphere::new(Vector3::default(), 1.0, material),
+            Matrix4::scaling(Vector3::new(2.0, 1.0, 1.0)),
+        )
+        .translated(Vector3::new(0.0, 0.0, -5.0))
+    }
+
+    #[test]
+    fn hits_in_world_space() {
+        let surface = ellipsoid();
+        let center = Vector3::new(0.0, 0.0, -5.0);
+        let (x, y) = (2f32.sqrt(), 0.5f32.sqrt());
+        let ray = Ray::new(center, Vector3::new(x, y, 0.0), 0.0);
+        let hit = surface.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((hit.t() - 1.0).abs() < 1e-5);
+        assert!((hit.point() - (center + Vector3::new(x, y, 0.0))).length() < 1e-5);
+        let expected = Vector3::new(1.0, 2.0, 0.0).normalized();
+        assert!((hit.normal() - expected).length() < 1e-5);
+
+        let bounds = surface.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.min(), Vector3::new(-2.0, -1.0, -6.0));
+        assert_eq!(bounds.max(), Vector3::new(2.0, 1.0, -4.0));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::math::{next_random, Rng};
+use crate::ray::Ray;
+use crate::surface::Surface;
+use crate::vector::Vector3;
+
+/// A single triangle with its own material. The surface coordinates of a hit
+/// are its barycentric coordinates with respect to the second and third
+/// vertices.
+#[derive(Debug)]
+pub struct Triangle {
+    v0: Vector3,
+    v1: Vector3,
+    v2: Vector3,
+    normal: Vector3,
+    material: Box<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(
+        v0: Vector3,
+        v1: Vector3,
+        v2: Vector3,
+        material: impl Material + 'static,
+    ) -> Triangle {
+        Triangle {
+            v0,
+            v1,
+            v2,
+            normal: (v1 - v0).cross(v2 - v0).normalized(),
+            material: Box::new(material),
+        }
+    }
+}
+
+impl Surface for Triangle {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(self.v0, self.v1, self.v2, ray, tmin, tmax)?;
+        let point = ray.point_at_parameter(t);
+        let hit = HitRecord::new(t, point, self.normal, b1, b2, self.material.as_ref());
+        Some(hit.with_derivatives(self.v1 - self.v0, self.v2 - self.v0))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(triangle_bounds(self.v0, self.v1, self.v2))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        let (t, _, _) = match intersect(self.v0, self.v1, self.v2, ray, 0.001, f32::MAX) {
+            Some(hit) => hit,
+            None => return 0.0,
+        };
+        let direction = ray.direction();
+        let distance_squared = t * t * direction.squared_norm();
+        let cosine = (direction.dot(self.normal) / direction.length()).abs();
+        let area = 0.5 * (self.v1 - self.v0).cross(self.v2 - self.v0).length();
+        if cosine * area > 0.0 {
+            // Convert the uniform density over the area to solid angle.
+            distance_squared / (cosine * area)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: Vector3, _time: f32, rng: &mut Rng) -> Vector3 {
+        // Warping the square onto the triangle this way keeps the points
+        // uniformly distributed over its area.
+        let s = next_random(rng).sqrt();
+        let b1 = s * (1.0 - next_random(rng));
+        let b2 = s - b1;
+        let point = (1.0 - s) * self.v0 + b1 * self.v1 + b2 * self.v2;
+        point - origin
+    }
+}
+
+/// Returns the smallest box enclosing the three vertices.
+#[inline]
+pub fn triangle_bounds(v0: Vector3, v1: Vector3, v2: Vector3) -> Aabb {
+    Aabb::new(v0.min(v1).min(v2), v0.max(v1).max(v2))
+}
+
+/// Intersects a ray with the triangle using the Möller-Trumbore algorithm,
+/// returning the ray parameter of the hit and the barycentric weights of the
+/// second and third vertices.
+#[inline]
+pub fn intersect(
+    v0: Vector3,
+    v1: Vector3,
+    v2: Vector3,
+    ray: &Ray,
+    tmin: f32,
+    tmax: f32,
+) -> Option<(f32, f32, f32)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = ray.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    if det == 0.0 {
+        // The ray is parallel to the plane of the triangle.
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.direction().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_det;
+    if t >= tmin && t < tmax {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hit_record::HitRecord;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::surface::triangle::{intersect, triangle_bounds};
+use crate::surface::{Bvh, Surface};
+use crate::vector::Vector3;
+
+/// A triangle of a mesh, given by indices into the vertex buffers of the mesh
+/// and into its list of materials.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshFace {
+    positions: [usize; 3],
+    normals: Option<[usize; 3]>,
+    uvs: Option<[usize; 3]>,
+    material: usize,
+}
+
+impl MeshFace {
+    pub fn new(
+        positions: [usize; 3],
+        normals: Option<[usize; 3]>,
+        uvs: Option<[usize; 3]>,
+        material: usize,
+    ) -> MeshFace {
+        MeshFace {
+            positions,
+            normals,
+            uvs,
+            material,
+        }
+    }
+
+    #[inline]
+    pub fn positions(&self) -> [usize; 3] {
+        self.positions
+    }
+
+    #[inline]
+    pub fn normals(&self) -> Option<[usize; 3]> {
+        self.normals
+    }
+
+    #[inline]
+    pub fn uvs(&self) -> Option<[usize; 3]> {
+        self.uvs
+    }
+
+    #[inline]
+    pub fn material(&self) -> usize {
+        self.material
+    }
+}
+
+/// The vertex buffers and materials shared by every triangle of a mesh.
+#[derive(Debug)]
+struct MeshData {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<[f32; 2]>,
+    faces: Vec<MeshFace>,
+    materials: Vec<Box<dyn Material>>,
+}
+
+/// A collection of triangles sharing vertex buffers. Triangles with vertex
+/// normals are shaded with the interpolated normal and triangles with vertex
+/// texture coordinates report interpolated surface coordinates.
+#[derive(Debug)]
+pub struct TriangleMesh {
+    triangles: Bvh,
+}
+
+impl TriangleMesh {
+    /// Builds a mesh from its vertex buffers and faces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a face refers to a vertex or material that does not exist.
+    pub fn new(
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        uvs: Vec<[f32; 2]>,
+        faces: Vec<MeshFace>,
+        materials: Vec<Box<dyn Material>>,
+    ) -> TriangleMesh {
+        for face in &faces {
+            assert!(face.positions.iter().all(|&i| i < positions.len()));
+            assert!(face
+                .normals
+                .map_or(true, |n| n.iter().all(|&i| i < normals.len())));
+            assert!(face.uvs.map_or(true, |t| t.iter().all(|&i| i < uvs.len())));
+            assert!(face.material < materials.len());
+        }
+        let count = faces.len();
+        let data = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        });
+        let triangles: Vec<Box<dyn Surface>> = (0..count)
+            .map(|index| {
+                Box::new(MeshTriangle {
+                    mesh: Arc::clone(&data),
+                    index,
+                }) as Box<dyn Surface>
+            })
+            .collect();
+        TriangleMesh {
+            triangles: Bvh::new(triangles, 0.0, 0.0),
+        }
+    }
+}
+
+impl Surface for TriangleMesh {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.triangles.hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.triangles.bounding_box(time0, time1)
+    }
+}
+
+/// One triangle of a mesh, referring back to the shared mesh data.
+#[derive(Debug)]
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl MeshTriangle {
+    #[inline]
+    fn vertices(&self) -> (Vector3, Vector3, Vector3) {
+        let [i0, i1, i2] = self.mesh.faces[self.index].positions;
+        let positions = &self.mesh.positions;
+        (positions[i0], positions[i1], positions[i2])
+    }
+}
+
+impl Surface for MeshTriangle {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let (v0, v1, v2) = self.vertices();
+        let (t, b1, b2) = intersect(v0, v1, v2, ray, tmin, tmax)?;
+        let b0 = 1.0 - b1 - b2;
+        let face = &self.mesh.faces[self.index];
+
+        let face_normal = (v1 - v0).cross(v2 - v0).normalized();
+        let normal = match face.normals {
+            Some([n0, n1, n2]) => {
+                let normals = &self.mesh.normals;
+                Some((b0 * normals[n0] + b1 * normals[n1] + b2 * normals[n2]).normalized())
+            }
+            None => None,
+        };
+        let ((u, v), (dpdu, dpdv)) = match face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                let uv = (
+                    b0 * uvs[t0][0] + b1 * uvs[t1][0] + b2 * uvs[t2][0],
+                    b0 * uvs[t0][1] + b1 * uvs[t1][1] + b2 * uvs[t2][1],
+                );
+                (
+                    uv,
+                    uv_derivatives((v0, v1, v2), (uvs[t0], uvs[t1], uvs[t2])),
+                )
+            }
+            None => ((b1, b2), (v1 - v0, v2 - v0)),
+        };
+
+        let point = ray.point_at_parameter(t);
+        let material = self.mesh.materials[face.material].as_ref();
+        let hit = match normal {
+            // The winding of the face may disagree with its vertex normals,
+            // which decide which side is the outside.
+            Some(normal) => {
+                let side = if face_normal.dot(normal) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                HitRecord::new(t, point, side * face_normal, u, v, material)
+                    .with_shading_normal(normal, ray)
+            }
+            None => HitRecord::new(t, point, face_normal, u, v, material),
+        };
+        Some(hit.with_derivatives(dpdu, dpdv))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let (v0, v1, v2) = self.vertices();
+        Some(triangle_bounds(v0, v1, v2))
+    }
+}
+
+/// Returns the rates of change of the point with the surface coordinates
+/// over a triangle with the given vertex positions and texture coordinates,
+/// or its edges from the first vertex if the coordinates do not span an area.
+fn uv_derivatives(
+    (p0, p1, p2): (Vector3, Vector3, Vector3),
+    (t0, t1, t2): ([f32; 2], [f32; 2], [f32; 2]),
+) -> (Vector3, Vector3) {
+    let (du02, dv02) = (t0[0] - t2[0], t0[1] - t2[1]);
+    let (du12, dv12) = (t1[0] - t2[0], t1[1] - t2[1]);
+    let determinant = du02 * dv12 - dv02 * du12;
+    if determinant.abs() < 1e-12 {
+        return (p1 - p0, p2 - p0);
+    }
+    let (dp02, dp12) = (p0 - p2, p1 - p2);
+    let inverse = 1.0 / determinant;
+    (
+        (dv12 * dp02 - dv02 * dp12) * inverse,
+        (du02 * dp12 - du12 * dp02) * inverse,
+    )
+}
@@ -3,8 +3,8 @@ use crate::vector::Vector3;
 
 #[derive(Debug)]
 pub struct CheckerTexture {
-    texture0: Box<Texture>,
-    texture1: Box<Texture>,
+    texture0: Box<dyn Texture>,
+    texture1: Box<dyn Texture>,
 }
 
 impl CheckerTexture {
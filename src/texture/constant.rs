@@ -8,7 +8,13 @@ pub struct ConstantTexture {
 
 impl ConstantTexture {
     pub fn new(color: Vector3) -> ConstantTexture {
-        ConstantTexture { color: color }
+        ConstantTexture { color }
+    }
+
+    /// Creates a texture holding the same value in every channel, as the
+    /// scalar parameters of materials are read from the red channel.
+    pub fn gray(value: f32) -> ConstantTexture {
+        ConstantTexture::new(Vector3::new(value, value, value))
     }
 
     #[inline]
@@ -0,0 +1,294 @@
+use png::{BitDepth, ColorType, Decoder, HasParameters, Transformations};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::texture::Texture;
+use crate::tonemap::srgb_decode;
+use crate::vector::Vector3;
+
+/// How the values stored in an image file relate to linear color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Values are encoded with the sRGB transfer function, as is usual for
+    /// artwork and photographs.
+    Srgb,
+    /// Values are stored linearly, as is usual for data such as normal maps.
+    Linear,
+}
+
+/// How texels are combined when a texture is sampled between their centers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Takes the texel containing the sample point.
+    Nearest,
+    /// Interpolates between the four texels nearest the sample point.
+    Bilinear,
+}
+
+/// How surface coordinates outside the unit square are mapped onto the
+/// image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Addressing {
+    /// Repeats the image.
+    Wrap,
+    /// Extends the edge texels outwards.
+    Clamp,
+    /// Repeats the image, flipping every other copy.
+    Mirror,
+}
+
+/// A texture looked up from an image by surface coordinates. The coordinate
+/// `u` runs from the left edge of the image to the right and `v` from the
+/// bottom edge to the top.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vector3>,
+    filter: Filter,
+    addressing: Addressing,
+}
+
+impl ImageTexture {
+    /// Creates a bilinearly filtered, wrapping texture from linear colors
+    /// given in rows from the top of the image down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty or the number of pixels does not match
+    /// its size.
+    pub fn new(width: u32, height: u32, pixels: Vec<Vector3>) -> ImageTexture {
+        assert!(width > 0 && height > 0);
+        assert_eq!(pixels.len(), (width * height) as usize);
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            filter: Filter::Bilinear,
+            addressing: Addressing::Wrap,
+        }
+    }
+
+    /// Loads a texture from a PNG file.
+    pub fn load<T>(path: T, color_space: ColorSpace) -> Result<ImageTexture, io::Error>
+    where
+        T: AsRef<Path>,
+    {
+        ImageTexture::read(File::open(path)?, color_space)
+    }
+
+    /// Decodes a texture from PNG data. Palette and grayscale images are
+    /// expanded to RGB and any alpha channel is ignored.
+    pub fn read<R: Read>(r: R, color_space: ColorSpace) -> Result<ImageTexture, io::Error> {
+        let mut decoder = Decoder::new(r);
+        decoder.set(Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        // Expansion leaves 16-bit images at 16 bits, whatever the reported
+        // depth, so the sample size is taken from the original header.
+        let sixteen_bit = reader.info().bit_depth == BitDepth::Sixteen;
+        let bytes = if sixteen_bit { 2 } else { 1 };
+        let channels = info.color_type.samples();
+        let sample = |index: usize| -> f32 {
+            let offset = index * bytes;
+            let value = if sixteen_bit {
+                f32::from(u16::from_be_bytes([data[offset], data[offset + 1]])) / 65535.0
+            } else {
+                f32::from(data[offset]) / 255.0
+            };
+            match color_space {
+                ColorSpace::Srgb => srgb_decode(value),
+                ColorSpace::Linear => value,
+            }
+        };
+
+        let count = (info.width * info.height) as usize;
+        let mut pixels = Vec::with_capacity(count);
+        for pixel in 0..count {
+            let first = pixel * channels;
+            pixels.push(match info.color_type {
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                    let gray = sample(first);
+                    Vector3::new(gray, gray, gray)
+                }
+                _ => Vector3::new(sample(first), sample(first + 1), sample(first + 2)),
+            });
+        }
+        if pixels.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "image is empty"));
+        }
+        Ok(ImageTexture::new(info.width, info.height, pixels))
+    }
+
+    /// Uses the given filter for lookups instead of bilinear filtering.
+    pub fn with_filter(self, filter: Filter) -> ImageTexture {
+        ImageTexture { filter, ..self }
+    }
+
+    /// Uses the given addressing mode instead of wrapping.
+    pub fn with_addressing(self, addressing: Addressing) -> ImageTexture {
+        ImageTexture { addressing, ..self }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the texel in column x counted from the left and row y counted
+    /// from the top, applying the addressing mode to indices outside the
+    /// image.
+    #[inline]
+    fn texel(&self, x: i64, y: i64) -> Vector3 {
+        let x = address(x, self.width, self.addressing);
+        let y = address(y, self.height, self.addressing);
+        self.pixels[y * self.width as usize + x]
+    }
+}
+
+/// The largest texel position kept before converting to an index, far past
+/// where single precision can tell neighbouring texels apart, so that the
+/// index of the next texel cannot overflow.
+const MAX_POSITION: f32 = 1e15;
+
+/// Maps a texel index onto the range of an image dimension.
+#[inline]
+fn address(index: i64, size: u32, addressing: Addressing) -> usize {
+    let size = i64::from(size);
+    let index = match addressing {
+        Addressing::Wrap => index.rem_euclid(size),
+        Addressing::Clamp => index.clamp(0, size - 1),
+        Addressing::Mirror => {
+            let index = index.rem_euclid(2 * size);
+            if index < size {
+                index
+            } else {
+                2 * size - 1 - index
+            }
+        }
+    };
+    index as usize
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _point: Vector3) -> Vector3 {
+        if !u.is_finite() || !v.is_finite() {
+            return Vector3::default();
+        }
+        // Texel centers lie at half integer positions.
+        let x = (u * self.width as f32).clamp(-MAX_POSITION, MAX_POSITION);
+        let y = ((1.0 - v) * self.height as f32).clamp(-MAX_POSITION, MAX_POSITION);
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+    use crate::tonemap::ToneMap;
+
+    fn gray(value: f32) -> Vector3 {
+        Vector3::new(value, value, value)
+    }
+
+    /// A two by one texture, black on the left and white on the right.
+    fn ramp() -> ImageTexture {
+        ImageTexture::new(2, 1, vec![gray(0.0), gray(1.0)])
+    }
+
+    #[test]
+    fn filters_between_texel_centers() {
+        let origin = Vector3::default();
+        let nearest = ramp().with_filter(Filter::Nearest);
+        assert_eq!(nearest.value(0.4, 0.5, origin), gray(0.0));
+        assert_eq!(nearest.value(0.6, 0.5, origin), gray(1.0));
+        let bilinear = ramp().with_addressing(Addressing::Clamp);
+        assert_eq!(bilinear.value(0.25, 0.5, origin), gray(0.0));
+        assert_eq!(bilinear.value(0.5, 0.5, origin), gray(0.5));
+        assert_eq!(bilinear.value(0.75, 0.5, origin), gray(1.0));
+        assert_eq!(bilinear.value(1.5, 0.5, origin), gray(1.0));
+    }
+
+    #[test]
+    fn addresses_outside_the_image() {
+        let sample = |addressing, u| {
+            ramp()
+                .with_filter(Filter::Nearest)
+                .with_addressing(addressing)
+                .value(u, 0.5, Vector3::default())
+                .x()
+        };
+        assert_eq!(sample(Addressing::Wrap, 1.25), 0.0);
+        assert_eq!(sample(Addressing::Wrap, -0.25), 1.0);
+        assert_eq!(sample(Addressing::Clamp, 1.25), 1.0);
+        assert_eq!(sample(Addressing::Clamp, -3.0), 0.0);
+        assert_eq!(sample(Addressing::Mirror, 1.25), 1.0);
+        assert_eq!(sample(Addressing::Mirror, 1.75), 0.0);
+        assert_eq!(sample(Addressing::Mirror, -0.25), 0.0);
+
+        let texture = ramp();
+        for &(u, v) in &[(f32::NAN, 0.5), (0.5, f32::INFINITY), (f32::MAX, f32::MIN)] {
+            let color = texture.value(u, v, Vector3::default());
+            assert!(color.x() >= 0.0 && color.x() <= 1.0, "{} {}", u, v);
+        }
+    }
+
+    #[test]
+    fn reads_png_as_linear_color() {
+        let mut image = Image::new(2, 2);
+        image.set_pixel(0, 0, Vector3::new(1.0, 0.0, 0.0));
+        image.set_pixel(1, 0, gray(0.2));
+        image.set_pixel(0, 1, gray(0.5));
+        let mut data = Vec::new();
+        image.write_png(&mut data, &ToneMap::default()).unwrap();
+
+        let texture = ImageTexture::read(data.as_slice(), ColorSpace::Srgb)
+            .unwrap()
+            .with_filter(Filter::Nearest);
+        let at = |u, v| texture.value(u, v, Vector3::default());
+        let close = |a: Vector3, b: Vector3| (a - b).length() < 0.005;
+        assert!(close(at(0.25, 0.75), Vector3::new(1.0, 0.0, 0.0)));
+        assert!(close(at(0.75, 0.75), gray(0.2)));
+        assert!(close(at(0.25, 0.25), gray(0.5)));
+        assert!(close(at(0.75, 0.25), gray(0.0)));
+
+        let linear = ImageTexture::read(data.as_slice(), ColorSpace::Linear).unwrap();
+        assert!((linear.pixels[2].x() - 0.735).abs() < 0.005);
+    }
+
+    #[test]
+    fn reads_sixteen_bit_grayscale() {
+        use png::Encoder;
+
+        let mut data = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut data, 2, 1);
+            encoder.set(ColorType::Grayscale).set(BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0x80, 0x00, 0xff, 0xff]).unwrap();
+        }
+        let texture = ImageTexture::read(data.as_slice(), ColorSpace::Linear).unwrap();
+        assert!((texture.pixels[0].x() - 0.5).abs() < 1e-4);
+        assert_eq!(texture.pixels[1], gray(1.0));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::texture::perlin::{Perlin, Turbulence};
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Marble, made of bands along the z axis whose phase is disturbed by
+/// turbulence so that they wander like veins in stone.
+#[derive(Debug)]
+pub struct MarbleTexture {
+    perlin: Perlin,
+    scale: f32,
+    strength: f32,
+    turbulence: Turbulence,
+    vein: Box<dyn Texture>,
+    base: Box<dyn Texture>,
+}
+
+impl MarbleTexture {
+    /// Creates marble with about `scale / π` veins per unit.
+    pub fn new(seed: u64, scale: f32, vein: impl Texture, base: impl Texture) -> MarbleTexture {
+        MarbleTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            strength: 10.0,
+            turbulence: Turbulence::default(),
+            vein: Box::new(vein),
+            base: Box::new(base),
+        }
+    }
+
+    /// Replaces the default octaves of noise.
+    pub fn with_turbulence(self, turbulence: Turbulence) -> MarbleTexture {
+        MarbleTexture { turbulence, ..self }
+    }
+
+    /// Sets how far the veins are pushed around by the turbulence, in
+    /// radians of phase. The default is 10.
+    pub fn with_strength(self, strength: f32) -> MarbleTexture {
+        MarbleTexture { strength, ..self }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        let turbulence = self.perlin.turbulence(self.scale * point, &self.turbulence);
+        let phase = self.scale * point.z() + self.strength * turbulence;
+        let t = 0.5 * (1.0 + phase.sin());
+        (1.0 - t) * self.vein.value(u, v, point) + t * self.base.value(u, v, point)
+    }
+}
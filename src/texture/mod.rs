@@ -4,10 +4,26 @@ use crate::vector::Vector3;
 
 mod checker;
 mod constant;
+mod image;
+mod marble;
+mod noise;
+mod perlin;
+mod wood;
 
 pub use self::checker::CheckerTexture;
 pub use self::constant::ConstantTexture;
+pub use self::image::{Addressing, ColorSpace, Filter, ImageTexture};
+pub use self::marble::MarbleTexture;
+pub use self::noise::NoiseTexture;
+pub use self::perlin::{Perlin, Turbulence};
+pub use self::wood::WoodTexture;
 
 pub trait Texture: Debug + Sync + Send + 'static {
     fn value(&self, u: f32, v: f32, point: Vector3) -> Vector3;
 }
+
+impl Texture for Box<dyn Texture> {
+    fn value(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        self.as_ref().value(u, v, point)
+    }
+}
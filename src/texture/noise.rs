@@ -0,0 +1,40 @@
+use crate::texture::perlin::{Perlin, Turbulence};
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Fractal Perlin noise blending between two textures.
+#[derive(Debug)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f32,
+    turbulence: Turbulence,
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+}
+
+impl NoiseTexture {
+    /// Creates noise with features about `1 / scale` units across, showing
+    /// `low` where the noise is lowest and `high` where it is highest.
+    pub fn new(seed: u64, scale: f32, low: impl Texture, high: impl Texture) -> NoiseTexture {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            turbulence: Turbulence::default(),
+            low: Box::new(low),
+            high: Box::new(high),
+        }
+    }
+
+    /// Replaces the default octaves of noise.
+    pub fn with_turbulence(self, turbulence: Turbulence) -> NoiseTexture {
+        NoiseTexture { turbulence, ..self }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        let noise = self.perlin.fractal(self.scale * point, &self.turbulence);
+        let t = 0.5 * (1.0 + noise);
+        (1.0 - t) * self.low.value(u, v, point) + t * self.high.value(u, v, point)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::math::{next_random, random_unit_vector, seeded_rng, Rng};
+use crate::vector::Vector3;
+
+/// Number of gradients in the lattice, which repeats after this many units.
+const POINT_COUNT: usize = 256;
+
+/// How octaves of noise are summed into fractal noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Turbulence {
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+}
+
+impl Turbulence {
+    /// Creates settings summing `octaves` layers of noise, each with its
+    /// frequency multiplied by `lacunarity` and its amplitude by `gain`
+    /// relative to the one before.
+    pub fn new(octaves: u32, lacunarity: f32, gain: f32) -> Turbulence {
+        Turbulence {
+            octaves,
+            lacunarity,
+            gain,
+        }
+    }
+
+    #[inline]
+    pub fn octaves(&self) -> u32 {
+        self.octaves
+    }
+
+    #[inline]
+    pub fn lacunarity(&self) -> f32 {
+        self.lacunarity
+    }
+
+    #[inline]
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+}
+
+impl Default for Turbulence {
+    fn default() -> Turbulence {
+        Turbulence::new(7, 2.0, 0.5)
+    }
+}
+
+/// Gradient noise on a lattice of random unit gradients, with the lattice
+/// shuffled by permutation tables drawn from a seed.
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: Vec<Vector3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = seeded_rng(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| random_unit_vector(&mut rng))
+            .collect();
+        let perm_x = permutation(&mut rng);
+        let perm_y = permutation(&mut rng);
+        let perm_z = permutation(&mut rng);
+        Perlin {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Returns the noise at the point, which lies between -1 and 1 and is
+    /// zero at the lattice points.
+    pub fn noise(&self, point: Vector3) -> f32 {
+        let (x, y, z) = (point.x().floor(), point.y().floor(), point.z().floor());
+        let fraction = point - Vector3::new(x, y, z);
+        let (i, j, k) = (x as i64, y as i64, z as i64);
+        let (u, v, w) = (fade(fraction.x()), fade(fraction.y()), fade(fraction.z()));
+
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+                    let offset = fraction - Vector3::new(di as f32, dj as f32, dk as f32);
+                    let weight = (if di == 1 { u } else { 1.0 - u })
+                        * (if dj == 1 { v } else { 1.0 - v })
+                        * (if dk == 1 { w } else { 1.0 - w });
+                    sum += weight * self.gradients[index].dot(offset);
+                }
+            }
+        }
+        // Unit gradients keep the sum within the half diagonal of a cell.
+        sum * (2.0 / 3f32.sqrt())
+    }
+
+    /// Returns fractal noise, a weighted sum of octaves of noise scaled back
+    /// to lie between -1 and 1.
+    pub fn fractal(&self, point: Vector3, turbulence: &Turbulence) -> f32 {
+        self.octaves(point, turbulence, |noise| noise)
+    }
+
+    /// Returns turbulence, a weighted sum of the magnitudes of octaves of
+    /// noise scaled back to lie between 0 and 1.
+    pub fn turbulence(&self, point: Vector3, turbulence: &Turbulence) -> f32 {
+        self.octaves(point, turbulence, f32::abs)
+    }
+
+    fn octaves<F>(&self, point: Vector3, turbulence: &Turbulence, shape: F) -> f32
+    where
+        F: Fn(f32) -> f32,
+    {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..turbulence.octaves {
+            sum += amplitude * shape(self.noise(frequency * point));
+            total += amplitude;
+            frequency *= turbulence.lacunarity;
+            amplitude *= turbulence.gain;
+        }
+        if total > 0.0 {
+            sum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Returns a random permutation of the lattice indices.
+fn permutation(rng: &mut Rng) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        let j = ((next_random(rng) * (i + 1) as f32) as usize).min(i);
+        permutation.swap(i, j);
+    }
+    permutation
+}
+
+/// Eases the interpolation weight so that the noise has continuous first
+/// and second derivatives across cell boundaries.
+#[inline]
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> impl Iterator<Item = Vector3> {
+        (0..1000).map(|i| {
+            let i = i as f32;
+            Vector3::new(0.37 * i - 100.0, 0.11 * i, -0.73 * i + 20.0)
+        })
+    }
+
+    #[test]
+    fn noise_is_reproducible_from_the_seed() {
+        let (a, b, c) = (Perlin::new(7), Perlin::new(7), Perlin::new(8));
+        assert!(points().all(|p| a.noise(p) == b.noise(p)));
+        assert!(points().any(|p| a.noise(p) != c.noise(p)));
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let perlin = Perlin::new(1);
+        let turbulence = Turbulence::default();
+        for p in points() {
+            assert!(perlin.noise(p).abs() <= 1.0);
+            assert!(perlin.fractal(p, &turbulence).abs() <= 1.0);
+            let t = perlin.turbulence(p, &turbulence);
+            assert!((0.0..=1.0).contains(&t));
+        }
+        let lattice = Vector3::new(3.0, -5.0, 12.0);
+        assert_eq!(perlin.noise(lattice), 0.0);
+        let single = Turbulence::new(1, 2.0, 0.5);
+        let p = Vector3::new(0.3, 0.6, 0.9);
+        assert_eq!(perlin.fractal(p, &single), perlin.noise(p));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::texture::perlin::{Perlin, Turbulence};
+use crate::texture::Texture;
+use crate::vector::Vector3;
+
+/// Wood, made of growth rings around the y axis whose radius is disturbed by
+/// turbulence.
+#[derive(Debug)]
+pub struct WoodTexture {
+    perlin: Perlin,
+    scale: f32,
+    strength: f32,
+    turbulence: Turbulence,
+    early: Box<dyn Texture>,
+    late: Box<dyn Texture>,
+}
+
+impl WoodTexture {
+    /// Creates wood with `scale` rings per unit, each fading from the `early`
+    /// texture on its inside to the `late` texture on its outside.
+    pub fn new(seed: u64, scale: f32, early: impl Texture, late: impl Texture) -> WoodTexture {
+        WoodTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            strength: 2.0,
+            turbulence: Turbulence::default(),
+            early: Box::new(early),
+            late: Box::new(late),
+        }
+    }
+
+    /// Replaces the default octaves of noise.
+    pub fn with_turbulence(self, turbulence: Turbulence) -> WoodTexture {
+        WoodTexture { turbulence, ..self }
+    }
+
+    /// Sets how far the rings are pushed around by the turbulence, in
+    /// rings. The default is 2.
+    pub fn with_strength(self, strength: f32) -> WoodTexture {
+        WoodTexture { strength, ..self }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, u: f32, v: f32, point: Vector3) -> Vector3 {
+        let turbulence = self.perlin.turbulence(point, &self.turbulence);
+        let radius = (point.x() * point.x() + point.z() * point.z()).sqrt();
+        let rings = self.scale * radius + self.strength * turbulence;
+        // Late wood is a thin dark band at the outside of each ring.
+        let t = (rings - rings.floor()).powi(4);
+        (1.0 - t) * self.early.value(u, v, point) + t * self.late.value(u, v, point)
+    }
+}
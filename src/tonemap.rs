@@ -0,0 +1,219 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vector::Vector3;
+
+/// Curve compressing scene radiance into the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneOperator {
+    /// Leaves radiance unchanged, so everything above 1 is left to the
+    /// overflow policy.
+    Clamp,
+    /// The Reinhard curve `x / (1 + x)` applied to each channel.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic reference rendering transform.
+    AcesFilmic,
+}
+
+impl ToneOperator {
+    #[inline]
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            ToneOperator::Clamp => x,
+            ToneOperator::Reinhard => x / (1.0 + x),
+            ToneOperator::AcesFilmic => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        }
+    }
+}
+
+impl FromStr for ToneOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ToneOperator, String> {
+        match s {
+            "clamp" => Ok(ToneOperator::Clamp),
+            "reinhard" => Ok(ToneOperator::Reinhard),
+            "aces" => Ok(ToneOperator::AcesFilmic),
+            _ => Err(format!("unknown tone operator '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ToneOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ToneOperator::Clamp => "clamp",
+            ToneOperator::Reinhard => "reinhard",
+            ToneOperator::AcesFilmic => "aces",
+        })
+    }
+}
+
+/// How colors still outside the displayable range after tone mapping are
+/// brought into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    /// Clamps each channel separately, which pushes bright saturated colors
+    /// towards white.
+    Clip,
+    /// Scales all channels by the brightest one, which keeps the hue of the
+    /// color at the cost of its brightness.
+    PreserveHue,
+}
+
+impl FromStr for Overflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Overflow, String> {
+        match s {
+            "clip" => Ok(Overflow::Clip),
+            "preserve-hue" => Ok(Overflow::PreserveHue),
+            _ => Err(format!("unknown overflow policy '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Overflow::Clip => "clip",
+            Overflow::PreserveHue => "preserve-hue",
+        })
+    }
+}
+
+/// The output transform turning linear scene radiance into 8-bit sRGB: an
+/// exposure adjustment, a tone curve, an overflow policy and the sRGB
+/// transfer function.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMap {
+    exposure: f32,
+    operator: ToneOperator,
+    overflow: Overflow,
+}
+
+impl ToneMap {
+    /// Creates a transform scaling radiance by `2^exposure` before applying
+    /// the tone operator.
+    pub fn new(exposure: f32, operator: ToneOperator, overflow: Overflow) -> ToneMap {
+        ToneMap {
+            exposure,
+            operator,
+            overflow,
+        }
+    }
+
+    /// Maps linear scene radiance to linear display values between 0 and 1.
+    pub fn apply(&self, color: Vector3) -> Vector3 {
+        let scale = 2f32.powf(self.exposure);
+        let map = |c: f32| {
+            // Negative and NaN radiance are treated as black.
+            let c = if c > 0.0 { c * scale } else { 0.0 };
+            self.operator.apply(c).max(0.0)
+        };
+        let mapped = Vector3::new(map(color.x()), map(color.y()), map(color.z()));
+        match self.overflow {
+            Overflow::Clip => Vector3::new(
+                mapped.x().min(1.0),
+                mapped.y().min(1.0),
+                mapped.z().min(1.0),
+            ),
+            Overflow::PreserveHue => {
+                let max = mapped.x().max(mapped.y()).max(mapped.z());
+                if max > 1.0 {
+                    mapped / max
+                } else {
+                    mapped
+                }
+            }
+        }
+    }
+
+    /// Maps linear scene radiance to 8-bit sRGB.
+    pub fn encode(&self, color: Vector3) -> [u8; 3] {
+        let c = self.apply(color);
+        let quantize = |v: f32| (255.0 * srgb_encode(v) + 0.5) as u8;
+        [quantize(c.x()), quantize(c.y()), quantize(c.z())]
+    }
+}
+
+impl Default for ToneMap {
+    fn default() -> ToneMap {
+        ToneMap::new(0.0, ToneOperator::Clamp, Overflow::Clip)
+    }
+}
+
+/// The sRGB transfer function, taking a linear value between 0 and 1 to its
+/// encoded value.
+#[inline]
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of the sRGB transfer function, taking an encoded value between
+/// 0 and 1 to its linear value.
+#[inline]
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_matches_reference_values() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.5) - 0.735_357).abs() < 1e-5);
+        assert!((srgb_encode(0.002) - 0.025_84).abs() < 1e-5);
+        let below = srgb_encode(0.003_130_8);
+        let above = srgb_encode(0.003_130_9);
+        assert!((above - below).abs() < 1e-5);
+        for i in 0..=100 {
+            let linear = i as f32 / 100.0;
+            assert!((srgb_decode(srgb_encode(linear)) - linear).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn operators_compress_into_display_range() {
+        for &operator in &[ToneOperator::Reinhard, ToneOperator::AcesFilmic] {
+            let map = ToneMap::new(0.0, operator, Overflow::Clip);
+            let mut previous = 0.0;
+            for i in 1..100 {
+                let value = map.apply(Vector3::new(i as f32 * 0.5, 0.0, 0.0)).x();
+                assert!(value >= previous && value <= 1.0, "{}", operator);
+                previous = value;
+            }
+            assert!(previous > 0.97, "{}", operator);
+        }
+    }
+
+    #[test]
+    fn highlights_saturate_instead_of_wrapping() {
+        let clip = ToneMap::default();
+        assert_eq!(clip.encode(Vector3::new(300.0, 1.5, 0.5)), [255, 255, 188]);
+        assert_eq!(clip.encode(Vector3::new(-1.0, f32::NAN, 0.0)), [0, 0, 0]);
+        let hue = ToneMap::new(0.0, ToneOperator::Clamp, Overflow::PreserveHue);
+        let c = hue.apply(Vector3::new(4.0, 2.0, 1.0));
+        assert_eq!(c, Vector3::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let map = ToneMap::new(-2.0, ToneOperator::Clamp, Overflow::Clip);
+        assert_eq!(
+            map.apply(Vector3::new(2.0, 1.0, 0.0)),
+            Vector3::new(0.5, 0.25, 0.0)
+        );
+    }
+}
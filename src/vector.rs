@@ -1,4 +1,5 @@
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vector3 {
@@ -69,6 +70,24 @@ impl Vector3 {
     pub fn sqrt(&self) -> Vector3 {
         Vector3::new(self.x.sqrt(), self.y.sqrt(), self.z.sqrt())
     }
+
+    #[inline]
+    pub fn min(&self, other: Vector3) -> Vector3 {
+        Vector3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    #[inline]
+    pub fn max(&self, other: Vector3) -> Vector3 {
+        Vector3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
 }
 
 impl Default for Vector3 {
@@ -77,6 +96,19 @@ impl Default for Vector3 {
     }
 }
 
+impl Index<usize> for Vector3 {
+    type Output = f32;
+
+    fn index(&self, axis: usize) -> &f32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("vector axis out of range: {}", axis),
+        }
+    }
+}
+
 impl Neg for Vector3 {
     type Output = Vector3;
 
@@ -133,3 +165,223 @@ impl Mul<Vector3> for f32 {
         Vector3::new(self * rhs.x, self * rhs.y, self * rhs.z)
     }
 }
+
+/// A 4x4 matrix acting on points and directions in homogeneous coordinates.
+/// Only affine transforms, whose bottom row is 0 0 0 1, are meaningful here.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    rows: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(rows: [[f32; 4]; 4]) -> Matrix4 {
+        Matrix4 { rows }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4::scaling(Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    /// Returns the transform moving points by the offset.
+    pub fn translation(offset: Vector3) -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the transform scaling each axis by the matching factor.
+    pub fn scaling(factors: Vector3) -> Matrix4 {
+        Matrix4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the transform rotating by the angle in degrees about the axis
+    /// through the origin, counterclockwise when looking down the axis
+    /// towards the origin.
+    pub fn rotation(axis: Vector3, degrees: f32) -> Matrix4 {
+        let a = axis.normalized();
+        let (sin, cos) = (degrees * PI / 180.0).sin_cos();
+        let k = 1.0 - cos;
+        Matrix4::new([
+            [
+                cos + a.x * a.x * k,
+                a.x * a.y * k - a.z * sin,
+                a.x * a.z * k + a.y * sin,
+                0.0,
+            ],
+            [
+                a.y * a.x * k + a.z * sin,
+                cos + a.y * a.y * k,
+                a.y * a.z * k - a.x * sin,
+                0.0,
+            ],
+            [
+                a.z * a.x * k - a.y * sin,
+                a.z * a.y * k + a.x * sin,
+                cos + a.z * a.z * k,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[inline]
+    pub fn get(&self, row: usize, column: usize) -> f32 {
+        self.rows[row][column]
+    }
+
+    #[inline]
+    pub fn transpose(&self) -> Matrix4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Matrix4::new(rows)
+    }
+
+    /// Returns the determinant of the upper left 3x3 block, the factor by
+    /// which an affine transform scales volumes.
+    #[inline]
+    pub fn determinant3(&self) -> f32 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns the inverse of an affine transform, or None if it collapses
+    /// space onto a plane, line or point.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let det = self.determinant3();
+        let k = 1.0 / det;
+        // A determinant so small that its reciprocal overflows is as
+        // singular as zero in single precision.
+        if !det.is_finite() || !k.is_finite() {
+            return None;
+        }
+        let m = &self.rows;
+        // The inverse of the linear part is its adjugate over the
+        // determinant, and the translation is undone after it.
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().take(3).enumerate() {
+            for (j, value) in row.iter_mut().take(3).enumerate() {
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                *value = k * (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]);
+            }
+        }
+        let inverse = Matrix4::new(rows);
+        let offset = inverse.transform_vector(Vector3::new(m[0][3], m[1][3], m[2][3]));
+        rows[0][3] = -offset.x;
+        rows[1][3] = -offset.y;
+        rows[2][3] = -offset.z;
+        rows[3][3] = 1.0;
+        Some(Matrix4::new(rows))
+    }
+
+    /// Transforms a position, which is moved by the translation.
+    #[inline]
+    pub fn transform_point(&self, point: Vector3) -> Vector3 {
+        self.transform_vector(point)
+            + Vector3::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+
+    /// Transforms a direction or offset, which is unaffected by the
+    /// translation.
+    #[inline]
+    pub fn transform_vector(&self, vector: Vector3) -> Vector3 {
+        let m = &self.rows;
+        Vector3::new(
+            m[0][0] * vector.x + m[0][1] * vector.y + m[0][2] * vector.z,
+            m[1][0] * vector.x + m[1][1] * vector.y + m[1][2] * vector.z,
+            m[2][0] * vector.x + m[2][1] * vector.y + m[2][2] * vector.z,
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Matrix4 {
+        Matrix4::identity()
+    }
+}
+
+/// Composes two transforms into one applying the right hand side first.
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Matrix4::new(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn transforms_points_and_directions() {
+        let rotation = Matrix4::rotation(Vector3::new(0.0, 1.0, 0.0), 90.0);
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        assert!(close(
+            rotation.transform_vector(x),
+            Vector3::new(0.0, 0.0, -1.0)
+        ));
+
+        let offset = Vector3::new(1.0, 2.0, 3.0);
+        let transform = Matrix4::translation(offset) * rotation;
+        assert!(close(
+            transform.transform_point(x),
+            Vector3::new(1.0, 2.0, 2.0)
+        ));
+        assert!(close(
+            transform.transform_vector(x),
+            Vector3::new(0.0, 0.0, -1.0)
+        ));
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Matrix4::translation(Vector3::new(1.0, -2.0, 0.5))
+            * Matrix4::rotation(Vector3::new(1.0, 1.0, 0.0), 30.0)
+            * Matrix4::scaling(Vector3::new(2.0, 0.5, 3.0));
+        let inverse = transform.inverse().unwrap();
+        let point = Vector3::new(0.3, -0.7, 1.1);
+        assert!(close(
+            inverse.transform_point(transform.transform_point(point)),
+            point
+        ));
+        let product = transform * inverse;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.get(i, j) - expected).abs() < 1e-5);
+            }
+        }
+        assert!((transform.determinant3() - 3.0).abs() < 1e-5);
+        assert!(Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+        assert!(Matrix4::scaling(Vector3::new(1e-13, 1e-13, 1e-13))
+            .inverse()
+            .is_none());
+    }
+}